@@ -211,7 +211,7 @@ impl<T: Hash + Eq> AttributeSet<T> {
     {
         let mut subset = self.empty_subset();
         for item in items {
-            subset.try_insert(&item).ok();
+            subset.try_insert(item).ok();
         }
         subset
     }
@@ -1,28 +1,302 @@
-use chord2key::input::device::*;
+use chord2key::control::{self, ControlRequest, ControlResponse, ControlServer};
+use chord2key::input::device::InputDevice;
+use chord2key::input::device_set::*;
+use chord2key::input::events::SourcedInputEvent;
+use chord2key::input::scripted::*;
+use chord2key::input::source::InputSource;
+use chord2key::logging::*;
+use chord2key::mapping::actions::Action;
+use chord2key::mapping::configuration::Configuration;
+use chord2key::mapping::layout::*;
 use chord2key::mapping::mapper::*;
+use chord2key::mapping::stats::*;
+use chord2key::mapping::training::*;
 use chord2key::output::device::*;
+use chord2key::output::passthrough::*;
+use chord2key::output::sink::NullSink;
+use chord2key::output::text::char_name;
+use log::{error, info, warn, LevelFilter};
 use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-fn print_usage_and_exit() {
-    eprintln!("Usage: chord2key [PATH_TO_CONFIG_FILE]");
+/// How often the main loop checks for a shutdown request while no input arrives.
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the usage statistics are saved while mapping, so a crash loses little.
+const STATS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many of the most and least used chords `stats` prints by default.
+const STATS_DEFAULT_TOP: usize = 10;
+
+fn print_usage_and_exit() -> ! {
+    eprintln!("Usage:");
+    eprintln!(
+        "    chord2key [LOG_OPTIONS] [--control-socket PATH] [--dry-run] PATH_TO_CONFIG_FILE"
+    );
+    eprintln!(
+        "    chord2key [--control-socket PATH] record --out PATH_TO_SESSION_FILE PATH_TO_CONFIG_FILE"
+    );
+    eprintln!(
+        "    chord2key [--control-socket PATH] replay [--realtime] [--emit] PATH_TO_SESSION_FILE PATH_TO_CONFIG_FILE"
+    );
+    eprintln!("    chord2key ctl --control-socket PATH COMMAND");
+    eprintln!("    chord2key stats [--top N] [--csv PATH] [--json PATH] PATH_TO_STATS_FILE");
+    eprintln!("    chord2key train [--words PATH_TO_WORD_LIST] PATH_TO_CONFIG_FILE");
+    eprintln!(
+        "    chord2key layout --spec PATH_TO_LAYOUT_SPEC --base PATH_TO_CONFIG_FILE [--out PATH] CORPUS_FILE..."
+    );
+    eprintln!();
+    eprintln!("Control commands:");
+    eprintln!("    status               Print the state of the running chord2key as JSON");
+    eprintln!("    switch PATH          Switch to a loaded configuration file");
+    eprintln!("    suspend | resume     Suspend or resume mapping");
+    eprintln!("    reload               Load every configuration file again");
+    eprintln!("    action JSON          Handle an action, such as '{{\"InnerAction\":\"Panic\"}}'");
+    eprintln!();
+    eprintln!("Stats options:");
+    eprintln!(
+        "    --top N              Print the N most and least used chords of each configuration"
+    );
+    eprintln!("    --csv PATH           Export every mapping as CSV, or to stdout if PATH is -");
+    eprintln!("    --json PATH          Export every mapping as JSON, or to stdout if PATH is -");
+    eprintln!();
+    eprintln!("Log options, which may appear anywhere:");
+    eprintln!("    --log-level LEVEL    One of off, error, warn, info, debug or trace");
+    eprintln!("    --log-json           Log one JSON object per line");
+    eprintln!("The log level can also be set with {}.", LOG_ENV_VAR);
     std::process::exit(1);
 }
-fn main() {
-    let args: Vec<_> = env::args().collect();
 
-    if args.len() > 2 {
-        eprintln!("Too many arguments!");
-        print_usage_and_exit();
+/// Stops on Ctrl+C or SIGTERM instead of dying, so held keys can be released.
+///
+/// Returns the flag that is set once a shutdown is requested.
+fn register_shutdown() -> Arc<AtomicBool> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, shutdown.clone())
+            .expect("Could not register the signal handlers");
+    }
+    shutdown
+}
+
+/// Creates a virtual copy of every input device to forward events to while suspended.
+fn mirror_devices(source: &dyn InputSource) -> Vec<PassthroughDevice> {
+    source
+        .devices()
+        .iter()
+        .map(PassthroughDevice::mirror)
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            error!("Could not create the passthrough devices: {}", e);
+            Vec::new()
+        })
+}
+
+/// Saves the usage statistics of the mapper, if it counts them.
+fn save_stats(mapper: &Mapper) {
+    if let Err(e) = mapper.save_stats() {
+        warn!("Could not save the usage statistics: {}", e);
+    }
+}
+
+/// Lets the mapper set the LEDs of the controllers behind the source, and no others.
+fn attach_leds(mapper: &mut Mapper, source: &dyn InputSource) {
+    let devices: Vec<PathBuf> = source
+        .devices()
+        .iter()
+        .filter_map(InputDevice::sysfs_parent)
+        .collect();
+    mapper.enable_leds(&devices);
+}
+
+/// Removes the log options from the arguments and installs the logger.
+///
+/// The level comes from `--log-level`, then the environment, and is info by default.
+fn init_logging(args: &mut Vec<String>) {
+    let mut level = level_from_env().unwrap_or(LevelFilter::Info);
+    let mut format = LogFormat::Text;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--log-json" => {
+                format = LogFormat::Json;
+                args.remove(i);
+            }
+            "--log-level" => {
+                match args.get(i + 1).and_then(|name| parse_level(name)) {
+                    Some(parsed) => level = parsed,
+                    None => {
+                        eprintln!("Missing or unknown log level!");
+                        print_usage_and_exit();
+                    }
+                }
+                args.drain(i..i + 2);
+            }
+            _ => i += 1,
+        }
     }
-    if args.len() < 2 {
-        eprintln!("No configuration file specified!");
+
+    Logger::new(level, format)
+        .install()
+        .expect("Could not install the logger");
+}
+
+/// Removes `--control-socket PATH` from the arguments, returning the path if it was given.
+fn take_control_socket(args: &mut Vec<String>) -> Option<String> {
+    let i = args.iter().position(|arg| arg == "--control-socket")?;
+    if i + 1 >= args.len() {
+        eprintln!("Missing control socket path!");
         print_usage_and_exit();
     }
-    let config_path = &args[1];
+    let path = args.remove(i + 1);
+    args.remove(i);
+    Some(path)
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    init_logging(&mut args);
+    let control_socket = take_control_socket(&mut args);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let exit_code = match args.as_slice() {
+        ["ctl", command @ ..] => match &control_socket {
+            Some(socket_path) => ctl(socket_path, command),
+            None => {
+                eprintln!("Missing control socket path!");
+                print_usage_and_exit()
+            }
+        },
+        ["stats", rest @ ..] if !rest.is_empty() => {
+            let (flags, stats_path) = rest.split_at(rest.len() - 1);
+            stats(stats_path[0], flags)
+        }
+        ["record", "--out", session_path, config_path] => {
+            let session = match File::create(session_path) {
+                Ok(file) => ScriptWriter::new(BufWriter::new(file)),
+                Err(e) => {
+                    error!("Could not create the session file {}: {}", session_path, e);
+                    std::process::exit(1);
+                }
+            };
+            info!("Recording input events to {}", session_path);
+            map_devices(config_path, Some(session), control_socket, false)
+        }
+        ["replay", rest @ ..] if rest.len() >= 2 => {
+            let (flags, paths) = rest.split_at(rest.len() - 2);
+            let realtime = flags.contains(&"--realtime");
+            let emit = flags.contains(&"--emit");
+            if flags.iter().any(|f| *f != "--realtime" && *f != "--emit") {
+                eprintln!("Unknown replay option!");
+                print_usage_and_exit();
+            }
+            replay(paths[0], paths[1], control_socket, realtime, emit)
+        }
+        ["train", "--words", words_path, config_path] => train(config_path, Some(words_path)),
+        ["train", config_path] if !config_path.starts_with('-') => train(config_path, None),
+        ["layout", rest @ ..] => layout(rest),
+        ["record", ..] | ["replay", ..] | ["stats"] | ["train", ..] => {
+            eprintln!("Missing or unexpected arguments!");
+            print_usage_and_exit()
+        }
+        ["--dry-run", config_path] => map_devices(config_path, None, control_socket, true),
+        [config_path] if !config_path.starts_with('-') => {
+            map_devices(config_path, None, control_socket, false)
+        }
+        [] => {
+            eprintln!("No configuration file specified!");
+            print_usage_and_exit()
+        }
+        _ => {
+            eprintln!("Unexpected arguments!");
+            print_usage_and_exit()
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+/// Creates the control socket if a path was given.
+fn bind_control_socket(path: Option<String>) -> Result<Option<ControlServer>, Box<dyn Error>> {
+    let control = path.map(ControlServer::bind).transpose()?;
+    if let Some(control) = &control {
+        info!(
+            "Listening for control commands on {}",
+            control.path().display()
+        );
+    }
+    Ok(control)
+}
+
+/// Sends a single command to a running chord2key through its control socket, printing the reply
+/// as JSON. Returns the exit code.
+fn ctl(socket_path: &str, command: &[&str]) -> i32 {
+    let request = match command {
+        ["status"] => ControlRequest::Status,
+        ["switch", path] => {
+            // The running process may have a different working directory
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+            ControlRequest::SwitchConfig(path)
+        }
+        ["suspend"] => ControlRequest::Suspend,
+        ["resume"] => ControlRequest::Resume,
+        ["reload"] => ControlRequest::Reload,
+        ["action", json] => match serde_json::from_str::<Action>(json) {
+            Ok(action) => ControlRequest::Action(action),
+            Err(e) => {
+                eprintln!("Badly formatted action: {}", e);
+                return 1;
+            }
+        },
+        _ => {
+            eprintln!("Unknown control command!");
+            print_usage_and_exit()
+        }
+    };
+
+    match control::request(socket_path, &request) {
+        Ok(response) => {
+            println!("{}", serde_json::to_string(&response).unwrap());
+            match response {
+                ControlResponse::Error(_) => 1,
+                _ => 0,
+            }
+        }
+        Err(e) => {
+            eprintln!("Could not reach chord2key at {}: {}", socket_path, e);
+            1
+        }
+    }
+}
 
-    println!("Starting keyboard + mouse emulation...");
-    let output_device = OutputDevice::init().expect("Could not initialize the Output Device");
-    println!("Started keyboard + mouse emulation!\n");
+/// Converts the configured input devices' events into keyboard+mouse events, optionally recording
+/// every input event and listening on a control socket. Returns the exit code.
+///
+/// A dry run creates no devices and doesn't grab the input devices. Instead, every resolved action
+/// is printed along with what produced it.
+fn map_devices(
+    config_path: &str,
+    mut session: Option<ScriptWriter<BufWriter<File>>>,
+    control_socket: Option<String>,
+    dry_run: bool,
+) -> i32 {
+    let shutdown = register_shutdown();
+
+    let output_device = if dry_run {
+        info!("Dry run: actions are printed instead of emitted");
+        OutputDevice::with_sink(Box::new(NullSink))
+    } else {
+        info!("Starting keyboard + mouse emulation...");
+        let device = OutputDevice::init().expect("Could not initialize the Output Device");
+        info!("Started keyboard + mouse emulation!");
+        device
+    };
 
     /*
     let config = chord2key::mapping::configuration::Configuration::r_joycon_mouse();
@@ -33,48 +307,657 @@ fn main() {
     config.save_to_file(config_path).ok();
     */
 
-    println!("Loading configuration file(s)...");
+    info!("Loading configuration file(s)...");
+    let mut mapper = Mapper::init_from_file(output_device, config_path).unwrap();
+    info!("Configuration file(s) successfully loaded!");
+
+    // A dry run shouldn't skew the statistics
+    if let Some(stats_path) = mapper.stats_path().filter(|_| !dry_run) {
+        match UsageStats::load_or_default(stats_path) {
+            Ok(stats) => {
+                info!("Counting mapping usage in {}", stats_path.display());
+                mapper.enable_stats(stats);
+            }
+            Err(e) => {
+                error!(
+                    "Could not load the usage statistics {}: {}",
+                    stats_path.display(),
+                    e
+                );
+                return 1;
+            }
+        }
+    }
+
+    if dry_run {
+        mapper.add_observer(Box::new(print_action));
+    }
+
+    let mut control = match bind_control_socket(control_socket) {
+        Ok(control) => control,
+        Err(e) => {
+            error!("Could not create the control socket: {}", e);
+            return 1;
+        }
+    };
+
+    let input_names = mapper
+        .get_input_selectors()
+        .iter()
+        .map(|selector| format!("[{}]", selector))
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!("Searching for {}...", input_names);
+    let mut input_devices = match InputDeviceSet::from_selectors(mapper.get_input_selectors()) {
+        Ok(devices) => devices,
+        Err(e) => {
+            error!("Could not find required input device: {}", e);
+            return 1;
+        }
+    };
+    info!("Successfully found {}!", input_names);
+
+    info!(
+        "chord2key will now convert {} events into keyboard+mouse events!",
+        input_names
+    );
+
+    let exit_code = run(
+        &mut mapper,
+        &mut input_devices,
+        &input_names,
+        &shutdown,
+        dry_run,
+        control.as_mut(),
+        &mut |ev| {
+            if let Some(writer) = &mut session {
+                if let Err(e) = writer.write(ev) {
+                    error!(
+                        "Could not record an input event, so recording stopped: {}",
+                        e
+                    );
+                    session = None;
+                }
+            }
+        },
+    );
+
+    info!("Shutting down...");
+    save_stats(&mapper);
+
+    // Dropping the mapper drops the output device, which releases all held keys and mouse motion
+    // before its thread exits. The same happens when unwinding from a panic.
+    drop(mapper);
+    exit_code
+}
+
+/// Feeds a recorded session through the mapper, printing the resolved actions or emitting the
+/// resulting output events. Returns the exit code.
+///
+/// The mapper measures time by the recorded event times, so chord repeats and counts play out the
+/// same however fast the session is replayed.
+fn replay(
+    session_path: &str,
+    config_path: &str,
+    control_socket: Option<String>,
+    realtime: bool,
+    emit: bool,
+) -> i32 {
+    let shutdown = register_shutdown();
+
+    let mut source = match ScriptedSource::load_from_file(session_path) {
+        Ok(source) => source.paced(realtime),
+        Err(e) => {
+            error!("Could not load the session file {}: {}", session_path, e);
+            return 1;
+        }
+    };
+
+    let output_device = if emit {
+        OutputDevice::init().expect("Could not initialize the Output Device")
+    } else {
+        OutputDevice::with_sink(Box::new(NullSink))
+    };
     let mut mapper = Mapper::init_from_file(output_device, config_path).unwrap();
-    println!("Configuration file(s) successfully loaded!\n");
-
-    println!("Searching for {}...", mapper.get_input_name());
-    let mut input_device = match InputDevice::from_name(mapper.get_input_name()) {
-        Some(device) => device,
-        None => {
-            eprintln!(
-                "Could not find required input device: {}",
-                mapper.get_input_name()
+    if !emit {
+        mapper.add_observer(Box::new(print_action));
+    }
+
+    let mut control = match bind_control_socket(control_socket) {
+        Ok(control) => control,
+        Err(e) => {
+            error!("Could not create the control socket: {}", e);
+            return 1;
+        }
+    };
+
+    let exit_code = run(
+        &mut mapper,
+        &mut source,
+        session_path,
+        &shutdown,
+        !emit,
+        control.as_mut(),
+        &mut |_ev| {},
+    );
+
+    // Releases everything left held, like when mapping devices
+    drop(mapper);
+    exit_code
+}
+
+/// Prints the most and least used chords of each configuration in a statistics file, and exports
+/// every mapping if asked to. Returns the exit code.
+fn stats(stats_path: &str, flags: &[&str]) -> i32 {
+    let mut top = STATS_DEFAULT_TOP;
+    let mut csv_path = None;
+    let mut json_path = None;
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match (*flag, flags.next()) {
+            ("--top", Some(n)) => match n.parse() {
+                Ok(n) => top = n,
+                Err(_) => {
+                    eprintln!("The number of chords must be a number!");
+                    print_usage_and_exit()
+                }
+            },
+            ("--csv", Some(path)) => csv_path = Some(*path),
+            ("--json", Some(path)) => json_path = Some(*path),
+            _ => {
+                eprintln!("Unknown or incomplete stats option!");
+                print_usage_and_exit()
+            }
+        }
+    }
+
+    let stats = match UsageStats::load_from_file(stats_path) {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("Could not load the usage statistics {}: {}", stats_path, e);
+            return 1;
+        }
+    };
+
+    // Keep stdout clean for an export
+    if csv_path != Some("-") && json_path != Some("-") {
+        print_stats(&stats, top);
+    }
+
+    let mut exports = Vec::new();
+    if let Some(path) = csv_path {
+        exports.push((path, stats.to_csv()));
+    }
+    if let Some(path) = json_path {
+        let rows = serde_json::to_string_pretty(&stats.rows()).unwrap_or_default();
+        exports.push((path, rows + "\n"));
+    }
+    for (path, text) in exports {
+        let result = match path {
+            "-" => std::io::stdout().write_all(text.as_bytes()),
+            path => std::fs::write(path, text),
+        };
+        if let Err(e) = result {
+            error!("Could not export the usage statistics to {}: {}", path, e);
+            return 1;
+        }
+    }
+    0
+}
+
+/// Prints the most and least used chords of every configuration.
+fn print_stats(stats: &UsageStats, top: usize) {
+    let print_chord = |(name, usage): &(&str, &Usage)| {
+        println!("    {:>8}  {} => {}", usage.count, name, usage.action);
+    };
+    for (layer, usage) in &stats.layers {
+        println!("{}", layer.display());
+        println!("  Unmapped chords: {}", usage.unmapped_chords);
+        if let Some(latency) = usage.mean_chord_latency_ms() {
+            println!("  Mean time from first press to emit: {:.1} ms", latency);
+        }
+
+        let ranked = usage.ranked_chords();
+        let most = &ranked[..top.min(ranked.len())];
+        let least = &ranked[most.len().max(ranked.len().saturating_sub(top))..];
+        if !most.is_empty() {
+            println!("  Most used chords:");
+            most.iter().for_each(print_chord);
+        }
+        if !least.is_empty() {
+            println!("  Least used chords:");
+            least.iter().rev().for_each(print_chord);
+        }
+    }
+}
+
+/// Quizzes the trainee on the chords of a configuration until Ctrl+C, then prints how well every
+/// character went. Returns the exit code.
+fn train(config_path: &str, words_path: Option<&str>) -> i32 {
+    let shutdown = register_shutdown();
+
+    let config = match Configuration::load_from_file(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(
+                "Could not load the configuration file {}: {}",
+                config_path, e
+            );
+            return 1;
+        }
+    };
+    let mut selectors = vec![config.main_device()];
+    selectors.extend(config.additional_devices.iter().cloned());
+
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut trainer = match Trainer::new(config, seed) {
+        Ok(trainer) => trainer,
+        Err(e) => {
+            error!("Could not train {}: {}", config_path, e);
+            return 1;
+        }
+    };
+    if let Some(words_path) = words_path {
+        match std::fs::read_to_string(words_path) {
+            Ok(words) => trainer = trainer.with_words(words.split_whitespace()),
+            Err(e) => {
+                error!("Could not read the word list {}: {}", words_path, e);
+                return 1;
+            }
+        }
+        if trainer.word_count() == 0 {
+            warn!(
+                "No word of {} can be typed with {}, so single characters are asked for instead",
+                words_path, config_path
             );
-            std::process::exit(1);
+        }
+    }
+
+    let input_names = selectors
+        .iter()
+        .map(|selector| format!("[{}]", selector))
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!("Searching for {}...", input_names);
+    let mut input_devices = match InputDeviceSet::from_selectors(&selectors) {
+        Ok(devices) => devices,
+        Err(e) => {
+            error!("Could not find required input device: {}", e);
+            return 1;
         }
     };
-    println!("Successfully found {}!\n", mapper.get_input_name());
+    // Keep other programs from reacting to the chords, such as a game launcher
+    if let Err(e) = input_devices.set_grab(true) {
+        warn!("Could not grab {}: {}", input_names, e);
+    }
 
     println!(
-        "chord2key will now convert {} events into keyboard+mouse events!",
-        mapper.get_input_name()
+        "Enter the chord of every character in brackets. Press Ctrl+C to stop and see your progress."
+    );
+    print_prompt(&trainer);
+    while !shutdown.load(Ordering::Relaxed) {
+        let result =
+            input_devices.poll_timeout(SHUTDOWN_CHECK_INTERVAL, &mut |ev: &SourcedInputEvent| {
+                if let Some(attempt) = trainer.handle_event(&ev.event, Instant::now()) {
+                    print_attempt(&attempt);
+                    print_prompt(&trainer);
+                }
+            });
+        if let Err(e) = result {
+            if e.raw_os_error() != Some(nix::errno::Errno::ENODEV as i32) {
+                error!("An unknown error occured: {:?}", e);
+                return 1;
+            }
+            warn!("An input device got disconnected!");
+            trainer.clear_chord();
+            info!("Waiting for {} to reconnect...", input_names);
+            if !input_devices.reconnect(&mut || shutdown.load(Ordering::Relaxed)) {
+                break;
+            }
+            info!("Reconnected to {}!", input_names);
+            if let Err(e) = input_devices.set_grab(true) {
+                warn!("Could not grab {}: {}", input_names, e);
+            }
+        }
+    }
+
+    println!();
+    print_progress(&trainer);
+    0
+}
+
+/// Prints the characters asked for, with the one expected next in brackets.
+fn print_prompt(trainer: &Trainer) {
+    let prompt: String = trainer
+        .prompt()
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            if i == trainer.position() {
+                format!("[{}]", char_name(*c))
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+    println!("{}", prompt);
+}
+
+/// Names the inputs of a chord, such as `BTN_EAST + BTN_SOUTH`.
+fn chord_text(chord: &[ChordInput]) -> String {
+    chord
+        .iter()
+        .map(ChordInput::to_string)
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Prints whether a chord typed the expected character, and the right chord if it didn't.
+fn print_attempt(attempt: &Attempt) {
+    match attempt {
+        Attempt::Hit { target, time } => {
+            println!("  Hit {} in {} ms", char_name(*target), time.as_millis());
+        }
+        Attempt::Miss {
+            target,
+            expected,
+            entered,
+            typed,
+        } => {
+            let typed = match typed {
+                Some(typed) => format!("types {}", char_name(*typed)),
+                None => "types nothing".to_string(),
+            };
+            println!(
+                "  Miss: {} {}, {} is typed with {}",
+                chord_text(entered),
+                typed,
+                char_name(*target),
+                chord_text(expected)
+            );
+        }
+    }
+}
+
+/// Prints the accuracy and speed of every character asked for, starting with the weakest.
+fn print_progress(trainer: &Trainer) {
+    let weakest = trainer.weakest();
+    if weakest.is_empty() {
+        println!("No chords were entered.");
+        return;
+    }
+    println!(
+        "{:>9}  {:>8}  {:>8}  {:>12}",
+        "Character", "Attempts", "Accuracy", "Mean time"
+    );
+    for c in weakest {
+        let progress = &trainer.progress()[&c];
+        let mean = match progress.mean_hit_ms() {
+            Some(ms) => format!("{:.0} ms", ms),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:>9}  {:>8}  {:>7.0}%  {:>12}",
+            char_name(c),
+            progress.attempts,
+            progress.accuracy().unwrap_or_default() * 100.0,
+            mean
+        );
+    }
+}
+
+/// Generates a configuration layer whose chords fit a corpus, writing it to a file or stdout.
+/// Returns the exit code.
+fn layout(args: &[&str]) -> i32 {
+    let mut spec_path = None;
+    let mut base_path = None;
+    let mut out_path = None;
+    let mut corpus_paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (*arg, arg.starts_with("--")) {
+            ("--spec", _) => spec_path = args.next(),
+            ("--base", _) => base_path = args.next(),
+            ("--out", _) => out_path = args.next(),
+            (_, true) => {
+                eprintln!("Unknown layout option!");
+                print_usage_and_exit()
+            }
+            (path, false) => corpus_paths.push(path),
+        }
+    }
+    let (spec_path, base_path) = match (spec_path, base_path) {
+        (Some(spec_path), Some(base_path)) if !corpus_paths.is_empty() => (spec_path, base_path),
+        _ => {
+            eprintln!("Missing or incomplete layout arguments!");
+            print_usage_and_exit()
+        }
+    };
+
+    let spec = match LayoutSpec::load_from_file(spec_path) {
+        Ok(spec) => spec,
+        Err(e) => {
+            error!(
+                "Could not load the layout specification {}: {}",
+                spec_path, e
+            );
+            return 1;
+        }
+    };
+    let base = match Configuration::load_from_file(base_path) {
+        Ok(base) => base,
+        Err(e) => {
+            error!("Could not load the configuration file {}: {}", base_path, e);
+            return 1;
+        }
+    };
+    let mut corpus = Corpus::default();
+    for path in corpus_paths {
+        match std::fs::read_to_string(path) {
+            Ok(text) => corpus.add_text(&text),
+            Err(e) => {
+                error!("Could not read the corpus {}: {}", path, e);
+                return 1;
+            }
+        }
+    }
+
+    let optimizer = LayoutOptimizer::new(&spec, &corpus);
+    info!(
+        "Assigning chords to {} keys from {} typed characters...",
+        optimizer.keys().len(),
+        corpus.total()
     );
+    let before = optimizer.effort(&optimizer.layout_of(&base));
+    let layer = match optimizer.generate(base) {
+        Ok(layer) => layer,
+        Err(e) => {
+            error!("Could not generate a layout: {}", e);
+            return 1;
+        }
+    };
+    let after = optimizer.effort(&optimizer.layout_of(&layer));
+    match (before, after) {
+        (Some(before), Some(after)) => info!(
+            "Expected effort per character: {:.3}, down from {:.3} in {}",
+            after, before, base_path
+        ),
+        (None, Some(after)) => info!("Expected effort per character: {:.3}", after),
+        _ => (),
+    }
+
+    let result = match out_path {
+        Some(path) => layer.save_to_file(path),
+        None => serde_json::to_string_pretty(&layer)
+            .map(|json| println!("{}", json))
+            .map_err(Into::into),
+    };
+    if let Err(e) = result {
+        error!("Could not write the generated configuration: {}", e);
+        return 1;
+    }
+    0
+}
+
+/// Prints a resolved action along with what produced it and the active configuration file, as
+/// well as every layer change.
+fn print_action(event: &MapperEvent) {
+    match event {
+        MapperEvent::ActionResolved {
+            action,
+            origin,
+            layer,
+        } => {
+            let layer = layer.file_name().unwrap_or(layer.as_os_str());
+            println!("[{}] {} => {:?}", layer.to_string_lossy(), origin, action);
+        }
+        MapperEvent::LayerChanged { layer, name } => {
+            println!("Layer: {} ({})", name, layer.display());
+        }
+        MapperEvent::ChordUnmapped { chord, layer } => {
+            let layer = layer.file_name().unwrap_or(layer.as_os_str());
+            let origin = ActionOrigin::UnmappedChord(chord.clone());
+            println!("[{}] {}", layer.to_string_lossy(), origin);
+        }
+        MapperEvent::SuspendChanged { .. } | MapperEvent::Error { .. } => {}
+    }
+}
 
-    loop {
-        let result = input_device.poll(|ev| {
-            mapper.handle_event(ev);
+/// Reports whether mapping got suspended or resumed, releasing everything held by the passthrough
+/// devices on resume.
+fn report_suspend_change(
+    was_suspended: bool,
+    mapper: &Mapper,
+    passthrough: &mut [PassthroughDevice],
+) {
+    match (was_suspended, mapper.is_suspended()) {
+        (false, true) => info!("Suspended mapping"),
+        (true, false) => {
+            info!("Resumed mapping");
+            passthrough.iter_mut().for_each(|device| {
+                device.release_all().ok();
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Feeds events from the source through the mapper until the source finishes, a shutdown is
+/// requested, or an unrecoverable error occurs. Every event is also shown to the observer, and
+/// control requests are answered between polls. Returns the exit code.
+///
+/// A dry run never grabs or rumbles the source, nor creates passthrough devices, sets the
+/// controller LEDs or runs the notifiers.
+fn run(
+    mapper: &mut Mapper,
+    source: &mut dyn InputSource,
+    input_names: &str,
+    shutdown: &AtomicBool,
+    dry_run: bool,
+    mut control: Option<&mut ControlServer>,
+    observer: &mut dyn FnMut(&SourcedInputEvent),
+) -> i32 {
+    let mut passthrough = Vec::new();
+    if mapper.wants_passthrough() && !dry_run {
+        passthrough = mirror_devices(source);
+    }
+    if !dry_run {
+        mapper.attach_rumble(source);
+        attach_leds(mapper, source);
+        mapper.enable_notifiers();
+    }
+
+    let mut grabbed = false;
+    let mut stats_saved = Instant::now();
+    while !shutdown.load(Ordering::Relaxed) && !source.is_finished() {
+        if stats_saved.elapsed() >= STATS_SAVE_INTERVAL {
+            save_stats(mapper);
+            stats_saved = Instant::now();
+        }
+
+        if mapper.wants_grab() != grabbed && !dry_run {
+            match source.set_grab(mapper.wants_grab()) {
+                Ok(()) if mapper.wants_grab() => info!("Grabbed {}", input_names),
+                Ok(()) => info!("Released {}", input_names),
+                Err(e) => error!("Could not change the grab on {}: {}", input_names, e),
+            }
+            grabbed = mapper.wants_grab();
+        }
+
+        // Wake up in time for a held chord to repeat
+        let timeout = match mapper.next_tick() {
+            Some(due) => due
+                .saturating_duration_since(Instant::now())
+                .min(SHUTDOWN_CHECK_INTERVAL),
+            None => SHUTDOWN_CHECK_INTERVAL,
+        };
+        let result = source.poll_timeout(timeout, &mut |ev| {
+            observer(ev);
+
+            // Chords due to repeat before a replayed event repeat first
+            if let Some(time) = ev.time {
+                mapper.tick(time);
+            }
+
+            let was_suspended = mapper.is_suspended();
+            mapper.handle_sourced_event(ev);
+            report_suspend_change(was_suspended, mapper, &mut passthrough);
+
+            if was_suspended && mapper.is_suspended() {
+                if let Some(device) = passthrough.get_mut(ev.source) {
+                    device.emit(ev).ok();
+                }
+            }
         });
+
+        mapper.tick(Instant::now());
+
+        if let Some(control) = control.as_deref_mut() {
+            let was_suspended = mapper.is_suspended();
+            control.handle_pending(mapper);
+            report_suspend_change(was_suspended, mapper, &mut passthrough);
+
+            // A reload may have added rumble to the configurations
+            if mapper.needs_rumble_backend() && !dry_run {
+                mapper.attach_rumble(source);
+            }
+        }
         if let Err(e) = result {
-            match e.kind() {
-                std::io::ErrorKind::Other => {
-                    if let Some(raw_os_err) = e.raw_os_error() {
-                        if raw_os_err == 19 {
-                            eprintln!("The device got disconnected!");
-                            std::process::exit(1);
-                        }
-                    }
+            if e.raw_os_error() == Some(nix::errno::Errno::ENODEV as i32) {
+                warn!("An input device got disconnected!");
+                mapper.report_error("An input device got disconnected");
+                mapper.reset();
+                passthrough.clear();
+
+                info!("Waiting for {} to reconnect...", input_names);
+                if !source.reconnect(&mut || shutdown.load(Ordering::Relaxed)) {
+                    // Giving up is only expected when shutting down
+                    return if shutdown.load(Ordering::Relaxed) {
+                        0
+                    } else {
+                        1
+                    };
                 }
-                _ => {
-                    eprintln!("An unknown error occured: ");
-                    eprintln!("{:?}", e);
-                    std::process::exit(1);
+                info!("Reconnected to {}!", input_names);
+
+                // A reopened device starts out without a grab
+                grabbed = false;
+                if mapper.wants_passthrough() && !dry_run {
+                    passthrough = mirror_devices(source);
+                }
+                if !dry_run {
+                    mapper.attach_rumble(source);
+                    // Reconnected controllers show up as new devices, with their LEDs reset
+                    attach_leds(mapper, source);
                 }
+            } else {
+                error!("An unknown error occured: {:?}", e);
+                return 1;
             }
         }
     }
+    0
 }
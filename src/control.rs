@@ -0,0 +1,437 @@
+use crate::constants::*;
+use crate::mapping::actions::Action;
+use crate::mapping::mapper::{ChordInput, Mapper};
+use log::{debug, warn};
+use nix::sys::stat::{umask, Mode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A command sent to a running chord2key process through its control socket.
+///
+/// Every request is a single JSON line, such as `"Status"`,
+/// `{"SwitchConfig": "/home/user/configs/numbers.json"}` or
+/// `{"Action": {"InnerAction": "Suspend"}}`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ControlRequest {
+    /// Report the current [ControlStatus].
+    Status,
+
+    /// Switch to one of the loaded configuration files.
+    SwitchConfig(PathBuf),
+
+    /// Suspend mapping.
+    Suspend,
+
+    /// Resume mapping.
+    Resume,
+
+    /// Load every configuration file again.
+    Reload,
+
+    /// Handle an action as if it was resolved from input.
+    Action(Action),
+}
+
+/// The reply to a [ControlRequest], sent back as a single JSON line.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ControlResponse {
+    /// The request was handled.
+    Ok,
+
+    /// The reply to [ControlRequest::Status].
+    Status(ControlStatus),
+
+    /// The request could not be handled, for the given reason.
+    Error(String),
+}
+
+/// A snapshot of the state of a running chord2key process.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ControlStatus {
+    /// The configuration file that is currently active.
+    pub config: PathBuf,
+
+    /// The display name of the active configuration, or its file name if it has none.
+    pub layer_name: String,
+
+    /// Whether mapping is suspended.
+    pub suspended: bool,
+
+    /// How many chords without a mapping were released since chord2key started.
+    pub unmapped_chords: u64,
+
+    /// The inputs of the chord currently being entered.
+    pub chord: Vec<ChordInput>,
+
+    /// The count entered for the next chord, if any. See
+    /// [InnerAction::CountDigit](crate::mapping::actions::InnerAction::CountDigit).
+    pub count: Option<u32>,
+
+    /// Every output key that is currently held down.
+    pub held_keys: Vec<KeyCode>,
+
+    /// The velocity of every moving mouse axis.
+    pub rel_axes: Vec<(RelAxisCode, AxisState)>,
+}
+
+/// Handles a single [ControlRequest] on the mapper, returning the reply.
+///
+/// # Example
+/// ```
+/// use chord2key::control::*;
+/// use chord2key::mapping::mapper::Mapper;
+/// use chord2key::output::device::OutputDevice;
+/// use chord2key::output::sink::NullSink;
+///
+/// let device = OutputDevice::with_sink(Box::new(NullSink));
+/// let config = "configs/nintendo_pro_controller/pro_keyboard.json";
+/// let mut mapper = Mapper::init_from_file(device, config).unwrap();
+///
+/// assert_eq!(respond(&mut mapper, ControlRequest::Suspend), ControlResponse::Ok);
+/// match respond(&mut mapper, ControlRequest::Status) {
+///     ControlResponse::Status(status) => assert!(status.suspended),
+///     other => panic!("Unexpected response {:?}", other),
+/// }
+/// ```
+pub fn respond(mapper: &mut Mapper, request: ControlRequest) -> ControlResponse {
+    let result: Result<(), Box<dyn Error>> = match request {
+        ControlRequest::Status => {
+            return match mapper.output_state() {
+                Ok(output) => ControlResponse::Status(ControlStatus {
+                    config: mapper.current_config_path().to_path_buf(),
+                    layer_name: mapper.current_layer_name().to_string(),
+                    suspended: mapper.is_suspended(),
+                    unmapped_chords: mapper.unmapped_chord_count(),
+                    chord: mapper.chord_state(),
+                    count: mapper.pending_count(),
+                    held_keys: output.held_keys,
+                    rel_axes: output.rel_axes,
+                }),
+                Err(e) => ControlResponse::Error(e.to_string()),
+            };
+        }
+        ControlRequest::SwitchConfig(path) => mapper.set_config(path),
+        ControlRequest::Suspend => {
+            mapper.set_suspended(true);
+            Ok(())
+        }
+        ControlRequest::Resume => {
+            mapper.set_suspended(false);
+            Ok(())
+        }
+        ControlRequest::Reload => mapper.reload(),
+        ControlRequest::Action(action) => {
+            mapper.inject_action(action);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => ControlResponse::Ok,
+        Err(e) => ControlResponse::Error(e.to_string()),
+    }
+}
+
+/// A single client connected to a [ControlServer].
+struct Connection {
+    stream: UnixStream,
+
+    /// Received bytes that don't make up a full line yet.
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    /// The longest request accepted, including its newline. A client sending a longer line is
+    /// disconnected, so it can't make the buffer grow without bound.
+    const MAX_LINE_LEN: usize = 64 * 1024;
+
+    /// How long sending a reply may block. A client that stops reading its replies is
+    /// disconnected once the socket buffer is full, instead of stalling the mapper.
+    const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Reads everything the client sent so far, answering every complete line.
+    ///
+    /// Returns whether the connection is still open.
+    fn handle_pending(&mut self, mapper: &mut Mapper) -> bool {
+        let mut chunk = [0u8; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    if !self.answer_lines(mapper) {
+                        return false;
+                    }
+                    if self.buffer.len() >= Self::MAX_LINE_LEN {
+                        let message =
+                            format!("Requests can't be longer than {} bytes", Self::MAX_LINE_LEN);
+                        warn!("Dropping a control connection: {}", message);
+                        self.send(&ControlResponse::Error(message)).ok();
+                        return false;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Answers every complete line in the buffer.
+    ///
+    /// Returns whether the replies could be sent.
+    fn answer_lines(&mut self, mapper: &mut Mapper) -> bool {
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str(&line) {
+                Ok(request) => {
+                    debug!("Control request {:?}", request);
+                    respond(mapper, request)
+                }
+                Err(e) => ControlResponse::Error(format!("Badly formatted request: {}", e)),
+            };
+            if self.send(&response).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn send(&mut self, response: &ControlResponse) -> Result<(), Box<dyn Error>> {
+        let mut line = serde_json::to_vec(response)?;
+        line.push(b'\n');
+
+        // Replies are small, so briefly block instead of queueing them, but never longer than the
+        // write timeout
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(&line);
+        self.stream.set_nonblocking(true)?;
+        Ok(result?)
+    }
+}
+
+/// A Unix domain socket that lets other programs inspect and command a running [Mapper].
+///
+/// Clients send [ControlRequest]s and receive [ControlResponse]s, one JSON object per line. Any
+/// number of requests may be sent over a connection. The server never blocks waiting for clients,
+/// so [ControlServer::handle_pending] is meant to be called regularly from the main loop.
+///
+/// Only the user running chord2key may connect, since any client can inject actions into the
+/// virtual devices. The socket file is removed when the server is dropped.
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+    connections: Vec<Connection>,
+}
+
+impl ControlServer {
+    /// Creates the socket at the given path.
+    ///
+    /// A leftover socket file from an earlier process is replaced. The socket is created readable and
+    /// writable by its owner only, so no one else can connect even right after it appears. Returns an error if another process is still listening on the
+    /// path, if the path is some other kind of file, or if the socket could not be created.
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                return Err(format!("{} exists and is not a socket", path.display()).into());
+            }
+            if UnixStream::connect(&path).is_ok() {
+                return Err(format!("{} is already in use", path.display()).into());
+            }
+            std::fs::remove_file(&path)?;
+        }
+
+        // Create the socket without access for anyone else, rather than restricting it after it
+        // already exists
+        let previous = umask(Mode::S_IRWXG | Mode::S_IRWXO | Mode::S_IXUSR);
+        let listener = UnixListener::bind(&path);
+        umask(previous);
+        let listener = listener?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path,
+            connections: Vec::new(),
+        })
+    }
+
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accepts new clients and answers every request received so far, without blocking.
+    pub fn handle_pending(&mut self, mapper: &mut Mapper) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _addr)) => {
+                    let configured = stream
+                        .set_nonblocking(true)
+                        .and_then(|()| stream.set_write_timeout(Some(Connection::WRITE_TIMEOUT)));
+                    if let Err(e) = configured {
+                        warn!("Could not accept a control connection: {}", e);
+                        continue;
+                    }
+                    self.connections.push(Connection {
+                        stream,
+                        buffer: Vec::new(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    warn!("Could not accept a control connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        self.connections
+            .retain_mut(|connection| connection.handle_pending(mapper));
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Sends a single request to the control socket at the given path, and waits for the reply.
+pub fn request<P: AsRef<Path>>(
+    path: P,
+    request: &ControlRequest,
+) -> Result<ControlResponse, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(path)?;
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.is_empty() {
+        return Err("The control socket closed without replying".into());
+    }
+    Ok(serde_json::from_str(&reply)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::actions::InnerAction;
+    use crate::output::device::OutputDevice;
+    use crate::output::sink::NullSink;
+    use crate::test_util::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    const PRO_KEYBOARD: &str = "configs/nintendo_pro_controller/pro_keyboard.json";
+
+    #[test]
+    fn socket_round_trip() {
+        let dir = TempDir::new("control");
+        let path = dir.join("control.sock");
+        let device = OutputDevice::with_sink(Box::new(NullSink));
+        let mut mapper = Mapper::init_from_file(device, PRO_KEYBOARD).unwrap();
+        let mut server = ControlServer::bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The mapper isn't Send, so the server runs here while the client runs on a thread
+        let client = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                vec![
+                    request(&path, &ControlRequest::Action(InnerAction::Suspend.into())).unwrap(),
+                    request(&path, &ControlRequest::Status).unwrap(),
+                    request(&path, &ControlRequest::SwitchConfig("/".into())).unwrap(),
+                ]
+            })
+        };
+        while !client.is_finished() {
+            server.handle_pending(&mut mapper);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let replies = client.join().unwrap();
+
+        assert_eq!(replies[0], ControlResponse::Ok);
+        match &replies[1] {
+            ControlResponse::Status(status) => {
+                assert!(status.suspended);
+                assert!(status.config.ends_with(PRO_KEYBOARD));
+                assert!(status.held_keys.is_empty());
+            }
+            other => panic!("Unexpected response {:?}", other),
+        }
+        assert!(matches!(replies[2], ControlResponse::Error(..)));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn overlong_requests_drop_the_connection() {
+        let dir = TempDir::new("control");
+        let path = dir.join("control.sock");
+        let device = OutputDevice::with_sink(Box::new(NullSink));
+        let mut mapper = Mapper::init_from_file(device, PRO_KEYBOARD).unwrap();
+        let mut server = ControlServer::bind(&path).unwrap();
+
+        let client = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut stream = UnixStream::connect(path).unwrap();
+                stream.write_all(&[b' '; Connection::MAX_LINE_LEN]).unwrap();
+                let mut replies = String::new();
+                BufReader::new(stream).read_to_string(&mut replies).unwrap();
+                replies
+            })
+        };
+        while !client.is_finished() {
+            server.handle_pending(&mut mapper);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        // A single error reply, after which the server hangs up
+        let replies = client.join().unwrap();
+        let mut lines = replies.lines();
+        let reply: ControlResponse = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert!(matches!(reply, ControlResponse::Error(..)));
+        assert_eq!(lines.next(), None);
+        assert!(server.connections.is_empty());
+    }
+
+    #[test]
+    fn clients_that_stop_reading_are_dropped() {
+        let dir = TempDir::new("control");
+        let path = dir.join("control.sock");
+        let device = OutputDevice::with_sink(Box::new(NullSink));
+        let mut mapper = Mapper::init_from_file(device, PRO_KEYBOARD).unwrap();
+        let mut server = ControlServer::bind(&path).unwrap();
+
+        // Keep asking for the status without ever reading a reply, until the replies fill the
+        // socket buffer
+        let mut stream = UnixStream::connect(&path).unwrap();
+        let batch = "\"Status\"\n".repeat(100);
+        server.handle_pending(&mut mapper);
+        for _ in 0..10_000 {
+            if server.connections.is_empty() {
+                break;
+            }
+            stream.write_all(batch.as_bytes()).unwrap();
+            let start = std::time::Instant::now();
+            server.handle_pending(&mut mapper);
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+        assert!(server.connections.is_empty());
+    }
+}
@@ -38,7 +38,6 @@ impl KeyEvent {
     /// let ev = KeyEvent::new(KeyCode::KEY_W, PressState::Down);     
     /// assert_eq!(ev.key(), KeyCode::KEY_W);
     /// ```
-
     pub fn key(&self) -> KeyCode {
         self.key
     }
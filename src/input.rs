@@ -1,2 +1,6 @@
 pub mod device;
+pub mod device_set;
 pub mod events;
+pub mod scripted;
+pub mod selector;
+pub mod source;
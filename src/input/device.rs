@@ -1,21 +1,82 @@
 //use super::types::*;
 //use crate::events::*;
 use super::events::*;
-use std::convert::TryFrom;
+use super::selector::*;
+use super::source::InputSource;
+use crate::output::rumble::DeviceRumble;
+use log::{log_enabled, trace, warn, Level};
+use nix::poll::{poll, PollFd, PollFlags};
+use std::convert::{TryFrom, TryInto};
 use std::io::prelude::*;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 /// A wrapper around input devices to simplify use for this crate.
 pub struct InputDevice {
     device: evdev::Device,
+    grabbed: bool,
+
+    /// The device node the device was opened from, such as /dev/input/event3, if known.
+    path: Option<PathBuf>,
 }
 
 impl From<evdev::Device> for InputDevice {
     fn from(evd: evdev::Device) -> Self {
-        Self { device: evd }
+        Self {
+            device: evd,
+            grabbed: false,
+            path: None,
+        }
+    }
+}
+
+impl Drop for InputDevice {
+    fn drop(&mut self) {
+        // The kernel releases the grab when the device is closed, but release it explicitly anyway
+        self.set_grab(false).ok();
+    }
+}
+
+impl AsRawFd for InputDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
     }
 }
 
 impl InputDevice {
+    /// Returns the underlying evdev device.
+    pub(crate) fn evdev_device(&self) -> &evdev::Device {
+        &self.device
+    }
+
+    /// Returns the name reported by the device, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.device.name()
+    }
+
+    /// Returns the sysfs directory of the device this input device belongs to, such as the HID
+    /// device of a controller, which also holds the controller's LEDs.
+    ///
+    /// Returns None if the device node isn't known or has no parent device in sysfs.
+    pub fn sysfs_parent(&self) -> Option<PathBuf> {
+        let node = self.path.as_ref()?.file_name()?;
+        Path::new("/sys/class/input")
+            .join(node)
+            .join("device/device")
+            .canonicalize()
+            .ok()
+    }
+
+    /// Wraps a device opened from the given device node.
+    fn opened(path: PathBuf, device: evdev::Device) -> Self {
+        Self {
+            device,
+            grabbed: false,
+            path: Some(path),
+        }
+    }
+
     /// Creates a new Device by searching for the given name.
     ///
     /// Returns Some(Device) if the name was found, otherwise None.
@@ -28,8 +89,62 @@ impl InputDevice {
     /// ```
     pub fn from_name(name: &str) -> Option<Self> {
         evdev::enumerate()
-            .find(|d| d.name() == Some(name))
-            .map(Self::from)
+            .find(|(_path, d)| d.name() == Some(name))
+            .map(|(path, d)| Self::opened(path, d))
+    }
+
+    /// Returns every available device that matches the selector, along with its information.
+    pub fn matching(selector: &DeviceSelector) -> Vec<(DeviceInfo, Self)> {
+        evdev::enumerate()
+            .map(|(path, d)| (DeviceInfo::from_device(path, &d), d))
+            .filter(|(info, _d)| selector.matches(info))
+            .map(|(info, d)| {
+                let device = Self::opened(info.path.clone(), d);
+                (info, device)
+            })
+            .collect()
+    }
+
+    /// Returns the information of every available device that matches the selector.
+    ///
+    /// # Example:
+    /// ```
+    /// use chord2key::input::device::*;
+    /// use chord2key::input::selector::*;
+    /// let none = InputDevice::candidates(&"No device".into());
+    /// assert!(none.is_empty());
+    /// ```
+    pub fn candidates(selector: &DeviceSelector) -> Vec<DeviceInfo> {
+        Self::matching(selector)
+            .into_iter()
+            .map(|(info, _d)| info)
+            .collect()
+    }
+
+    /// Creates a new Device from the only available device matching the selector.
+    ///
+    /// Returns an error listing the relevant devices if no device or multiple devices match.
+    ///
+    /// # Example:
+    /// ```
+    /// use chord2key::input::device::*;
+    /// use chord2key::input::selector::*;
+    /// let none = InputDevice::from_selector(&"No device".into());
+    /// assert!(matches!(none, Err(SelectorError::NoMatch(..))));
+    /// ```
+    pub fn from_selector(selector: &DeviceSelector) -> Result<Self, SelectorError> {
+        let mut matching = Self::matching(selector);
+        match matching.len() {
+            0 => {
+                let available = Self::candidates(&DeviceSelector::default());
+                Err(SelectorError::NoMatch(selector.clone(), available))
+            }
+            1 => Ok(matching.remove(0).1),
+            _ => {
+                let infos = matching.into_iter().map(|(info, _d)| info).collect();
+                Err(SelectorError::Ambiguous(selector.clone(), infos))
+            }
+        }
     }
 
     /// Creates a new Device by querying the user through the CLI.
@@ -37,7 +152,7 @@ impl InputDevice {
     /// Returns the Device chosen by the user.
     pub fn from_cli() -> Self {
         let devices = evdev::enumerate().collect::<Vec<_>>();
-        for (i, d) in devices.iter().enumerate() {
+        for (i, (_path, d)) in devices.iter().enumerate() {
             println!("{}: {}", i, d.name().unwrap_or("Unnamed device"));
         }
         print!("Select the device [0-{}]: ", devices.len() - 1);
@@ -45,9 +160,49 @@ impl InputDevice {
         let mut chosen = String::new();
         std::io::stdin().read_line(&mut chosen).unwrap();
         let n = chosen.trim().parse::<usize>().unwrap();
-        let device = devices.into_iter().nth(n).unwrap();
+        let (path, device) = devices.into_iter().nth(n).unwrap();
+
+        Self::opened(path, device)
+    }
 
-        Self { device }
+    /// Returns whether this process has an exclusive grab on the device.
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Takes or releases an exclusive grab on the device.
+    ///
+    /// While grabbed, events from the device are only delivered to this process, so they can't
+    /// trigger actions in games or the desktop. Does nothing if the device is already in the
+    /// requested state.
+    pub fn set_grab(&mut self, grab: bool) -> Result<(), std::io::Error> {
+        if grab == self.grabbed {
+            return Ok(());
+        }
+        if grab {
+            self.device.grab()?;
+        } else {
+            self.device.ungrab()?;
+        }
+        self.grabbed = grab;
+        Ok(())
+    }
+
+    /// Returns whether the device can play rumble effects.
+    pub fn supports_rumble(&self) -> bool {
+        self.device
+            .supported_ff()
+            .is_some_and(|ff| ff.contains(evdev::FFEffectType::FF_RUMBLE))
+    }
+
+    /// Uploads a rumble effect to the device, returning a handle that plays it.
+    ///
+    /// Returns an error if the device has no room for another effect, or can't rumble at all.
+    pub fn upload_rumble(&mut self) -> Result<DeviceRumble, std::io::Error> {
+        let effect = self
+            .device
+            .upload_ff_effect(DeviceRumble::silent_effect())?;
+        Ok(DeviceRumble::new(effect))
     }
 
     /// Polls the device for events, sending valid events to a closure.
@@ -57,16 +212,140 @@ impl InputDevice {
     where
         F: FnMut(&InputEvent),
     {
+        self.poll_frames(|event, _frame_end| callback(event))
+    }
+
+    /// Polls the device for events like [InputDevice::poll], also telling the closure whether each
+    /// event is the last valid event before the device's SYN_REPORT.
+    pub fn poll_frames<F>(&mut self, callback: F) -> Result<(), std::io::Error>
+    where
+        F: FnMut(&InputEvent, bool),
+    {
+        // Only look up the name when raw events are traced, since it can't be borrowed while fetching
+        let name = log_enabled!(Level::Trace)
+            .then(|| self.device.name().unwrap_or("unnamed device").to_string());
         let events = self.device.fetch_events()?;
 
         //let start = std::time::Instant::now();
-        for event in events {
-            if let Ok(input) = InputEvent::try_from(event) {
-                callback(&input);
+        let events = events.inspect(|event| {
+            if let Some(name) = &name {
+                let age = SystemTime::now()
+                    .duration_since(event.timestamp())
+                    .unwrap_or_default();
+                trace!(
+                    "Raw event {:?} from {}, {}us old",
+                    event,
+                    name,
+                    age.as_micros()
+                );
             }
-        }
+        });
+        send_framed(events, callback);
         //let duration = start.elapsed();
         //println!("Time elapsed to handle event is: {:?}", duration);
         Ok(())
     }
 }
+
+/// Sends the valid events to the closure, along with whether each is the last valid event before a
+/// SYN_REPORT.
+fn send_framed<I, F>(events: I, mut callback: F)
+where
+    I: IntoIterator<Item = evdev::InputEvent>,
+    F: FnMut(&InputEvent, bool),
+{
+    // Hold each event back until it's known whether the frame ends after it
+    let mut pending: Option<InputEvent> = None;
+    for event in events {
+        if event.event_type() == evdev::EventType::SYNCHRONIZATION
+            && event.code() == evdev::Synchronization::SYN_REPORT.0
+        {
+            if let Some(input) = pending.take() {
+                callback(&input, true);
+            }
+        } else if let Ok(input) = InputEvent::try_from(event) {
+            if let Some(previous) = pending.replace(input) {
+                callback(&previous, false);
+            }
+        }
+    }
+    if let Some(input) = pending {
+        callback(&input, true);
+    }
+}
+
+impl InputSource for InputDevice {
+    fn poll_timeout(
+        &mut self,
+        timeout: Duration,
+        callback: &mut dyn FnMut(&SourcedInputEvent),
+    ) -> Result<(), std::io::Error> {
+        let mut fds = [PollFd::new(self.as_raw_fd(), PollFlags::POLLIN)];
+        let timeout = timeout.as_millis().try_into().unwrap_or(i32::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(nix::errno::Errno::EINTR) => Ok(()),
+            Ok(_) => self.poll_frames(|event, frame_end| {
+                callback(&SourcedInputEvent {
+                    source: 0,
+                    event: event.clone(),
+                    frame_end,
+                    time: None,
+                })
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn devices(&self) -> &[InputDevice] {
+        std::slice::from_ref(self)
+    }
+
+    fn set_grab(&mut self, grab: bool) -> Result<(), std::io::Error> {
+        InputDevice::set_grab(self, grab)
+    }
+
+    fn rumble_effects(&mut self) -> Vec<DeviceRumble> {
+        if !self.supports_rumble() {
+            return Vec::new();
+        }
+        match InputDevice::upload_rumble(self) {
+            Ok(rumble) => vec![rumble],
+            Err(e) => {
+                warn!("Could not upload a rumble effect: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_end_at_syn_report() {
+        let abs = |axis: evdev::AbsoluteAxisType, value| {
+            evdev::InputEvent::new(evdev::EventType::ABSOLUTE, axis.0, value)
+        };
+        let syn = evdev::InputEvent::new(
+            evdev::EventType::SYNCHRONIZATION,
+            evdev::Synchronization::SYN_REPORT.0,
+            0,
+        );
+        let scan = evdev::InputEvent::new(evdev::EventType::MISC, 4, 90001);
+        let events = vec![
+            abs(evdev::AbsoluteAxisType::ABS_X, 10),
+            abs(evdev::AbsoluteAxisType::ABS_Y, 20),
+            scan,
+            syn,
+            abs(evdev::AbsoluteAxisType::ABS_X, 30),
+            syn,
+            // A frame without a SYN_REPORT yet still ends with the fetched events
+            abs(evdev::AbsoluteAxisType::ABS_Y, 40),
+        ];
+
+        let mut frame_ends = Vec::new();
+        send_framed(events, |_event, frame_end| frame_ends.push(frame_end));
+        assert_eq!(frame_ends, [false, true, true, true]);
+    }
+}
@@ -0,0 +1,285 @@
+use super::device::*;
+use super::events::*;
+use super::selector::*;
+use super::source::InputSource;
+use crate::output::rumble::DeviceRumble;
+use log::debug;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::epoll::*;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use std::convert::TryInto;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+/// A group of input devices that are polled together.
+///
+/// Events from every device are tagged with the index of their source device, in the order the
+/// devices were given.
+pub struct InputDeviceSet {
+    devices: Vec<InputDevice>,
+    epoll_fd: RawFd,
+
+    /// The selectors the devices were found with, used to find them again after a disconnect.
+    selectors: Vec<DeviceSelector>,
+}
+
+impl InputDeviceSet {
+    /// Creates a new InputDeviceSet from already opened devices.
+    ///
+    /// Returns an error if the devices could not be registered for polling.
+    pub fn new(devices: Vec<InputDevice>) -> Result<Self, std::io::Error> {
+        let epoll_fd = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?;
+        // Construct early so the epoll file descriptor is closed on errors
+        let set = Self {
+            devices,
+            epoll_fd,
+            selectors: Vec::new(),
+        };
+        for (i, device) in set.devices.iter().enumerate() {
+            let mut event = EpollEvent::new(EpollFlags::EPOLLIN, i as u64);
+            epoll_ctl(
+                set.epoll_fd,
+                EpollOp::EpollCtlAdd,
+                device.as_raw_fd(),
+                &mut event,
+            )?;
+        }
+        Ok(set)
+    }
+
+    /// Creates a new InputDeviceSet from the only device matching each selector.
+    ///
+    /// Returns Err describing the first selector that didn't match exactly one device, or
+    /// Ok(InputDeviceSet) if all of them did.
+    ///
+    /// # Example:
+    /// ```
+    /// use chord2key::input::device_set::*;
+    /// use chord2key::input::selector::*;
+    /// let none = InputDeviceSet::from_selectors(&["No device".into()]);
+    /// assert!(none.is_err());
+    /// ```
+    pub fn from_selectors(
+        selectors: &[DeviceSelector],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let devices = selectors
+            .iter()
+            .map(InputDevice::from_selector)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut set = Self::new(devices)?;
+        set.selectors = selectors.to_vec();
+        Ok(set)
+    }
+
+    /// The amount of time between searches while waiting for devices to appear.
+    ///
+    /// Device creation is also watched for through inotify on /dev/input when possible, so this is
+    /// mostly a fallback, and a retry in case a device node was found before it could be opened.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::input::device_set::InputDeviceSet;
+    /// use std::time::Duration;
+    /// assert_eq!(InputDeviceSet::search_interval(), Duration::from_millis(1000));
+    /// ```
+    pub const fn search_interval() -> Duration {
+        Duration::from_millis(1000)
+    }
+
+    /// Blocks until every selected device is available, then creates a new InputDeviceSet from
+    /// them.
+    ///
+    /// This is used to wait for devices that got disconnected, such as a gamepad that dropped its
+    /// bluetooth connection.
+    pub fn wait_for_selectors(selectors: &[DeviceSelector]) -> Self {
+        Self::wait_for_selectors_until(selectors, || false)
+            .expect("Waiting without a stop condition can't stop early")
+    }
+
+    /// Waits for every selected device like [InputDeviceSet::wait_for_selectors], but gives up
+    /// once `stop` returns true.
+    ///
+    /// `stop` is checked at least once every [InputDeviceSet::search_interval], and whenever a
+    /// signal interrupts the wait. Returns None if the wait was given up.
+    pub fn wait_for_selectors_until<F>(selectors: &[DeviceSelector], mut stop: F) -> Option<Self>
+    where
+        F: FnMut() -> bool,
+    {
+        let watcher = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .and_then(|inotify| {
+                match inotify.add_watch(
+                    "/dev/input",
+                    AddWatchFlags::IN_CREATE | AddWatchFlags::IN_ATTRIB,
+                ) {
+                    Ok(_) => Ok(inotify),
+                    Err(e) => {
+                        nix::unistd::close(inotify.as_raw_fd()).ok();
+                        Err(e)
+                    }
+                }
+            })
+            .ok();
+
+        let set = loop {
+            match Self::from_selectors(selectors) {
+                Ok(set) => break Some(set),
+                Err(e) => debug!("Still waiting for the input devices: {}", e),
+            }
+            if stop() {
+                break None;
+            }
+            match watcher {
+                Some(inotify) => {
+                    // Wake up on any new or changed device node, or after the search interval
+                    let mut fds = [PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN)];
+                    let timeout = Self::search_interval().as_millis() as i32;
+                    if let Ok(n) = poll(&mut fds, timeout) {
+                        if n > 0 {
+                            inotify.read_events().ok();
+                        }
+                    }
+                }
+                None => std::thread::sleep(Self::search_interval()),
+            }
+        };
+
+        if let Some(inotify) = watcher {
+            nix::unistd::close(inotify.as_raw_fd()).ok();
+        }
+        set
+    }
+
+    /// Returns the number of devices in the set.
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns `true` if the set contains no devices.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns the devices in the set, indexed by [SourcedInputEvent::source].
+    pub fn devices(&self) -> &[InputDevice] {
+        &self.devices
+    }
+
+    /// Returns whether this process has an exclusive grab on every device.
+    pub fn is_grabbed(&self) -> bool {
+        self.devices.iter().all(|device| device.is_grabbed())
+    }
+
+    /// Takes or releases an exclusive grab on every device. See [InputDevice::set_grab].
+    pub fn set_grab(&mut self, grab: bool) -> Result<(), std::io::Error> {
+        self.devices
+            .iter_mut()
+            .try_for_each(|device| device.set_grab(grab))
+    }
+
+    /// Polls all devices for events, sending valid events to a closure.
+    ///
+    /// This will block until an event -- valid or invalid -- occurs on any of the devices.
+    pub fn poll<F>(&mut self, callback: F) -> Result<(), std::io::Error>
+    where
+        F: FnMut(&SourcedInputEvent),
+    {
+        self.poll_inner(-1, true, callback)
+    }
+
+    /// Polls all devices for events like [InputDeviceSet::poll], but gives up after the timeout.
+    ///
+    /// This also returns early if the process receives a signal, so the caller can react to it.
+    pub fn poll_timeout<F>(&mut self, timeout: Duration, callback: F) -> Result<(), std::io::Error>
+    where
+        F: FnMut(&SourcedInputEvent),
+    {
+        let timeout = timeout.as_millis().try_into().unwrap_or(isize::MAX);
+        self.poll_inner(timeout, false, callback)
+    }
+
+    fn poll_inner<F>(
+        &mut self,
+        timeout: isize,
+        retry_interrupted: bool,
+        mut callback: F,
+    ) -> Result<(), std::io::Error>
+    where
+        F: FnMut(&SourcedInputEvent),
+    {
+        let mut ready = vec![EpollEvent::empty(); self.devices.len()];
+        let n = loop {
+            match epoll_wait(self.epoll_fd, &mut ready, timeout) {
+                Err(nix::errno::Errno::EINTR) if retry_interrupted => continue,
+                Err(nix::errno::Errno::EINTR) => return Ok(()),
+                res => break res?,
+            }
+        };
+
+        // Read every ready device before reporting an error, so a disconnecting device doesn't
+        // lose the events, such as releases, that the others already sent
+        let mut result = Ok(());
+        for epoll_event in &ready[..n] {
+            let source = epoll_event.data() as usize;
+            let polled = self.devices[source].poll_frames(|event, frame_end| {
+                callback(&SourcedInputEvent {
+                    source,
+                    event: event.clone(),
+                    frame_end,
+                    time: None,
+                })
+            });
+            if result.is_ok() {
+                result = polled;
+            }
+        }
+        result
+    }
+}
+
+impl InputSource for InputDeviceSet {
+    fn poll_timeout(
+        &mut self,
+        timeout: Duration,
+        callback: &mut dyn FnMut(&SourcedInputEvent),
+    ) -> Result<(), std::io::Error> {
+        InputDeviceSet::poll_timeout(self, timeout, callback)
+    }
+
+    fn devices(&self) -> &[InputDevice] {
+        &self.devices
+    }
+
+    fn set_grab(&mut self, grab: bool) -> Result<(), std::io::Error> {
+        InputDeviceSet::set_grab(self, grab)
+    }
+
+    fn rumble_effects(&mut self) -> Vec<DeviceRumble> {
+        self.devices
+            .iter_mut()
+            .flat_map(InputSource::rumble_effects)
+            .collect()
+    }
+
+    /// Waits for the devices to reappear, if the set was created from selectors.
+    fn reconnect(&mut self, stop: &mut dyn FnMut() -> bool) -> bool {
+        if self.selectors.is_empty() {
+            return false;
+        }
+
+        // Close the remaining devices so they can be reopened together
+        self.devices.clear();
+        match Self::wait_for_selectors_until(&self.selectors, stop) {
+            Some(set) => {
+                *self = set;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for InputDeviceSet {
+    fn drop(&mut self) {
+        nix::unistd::close(self.epoll_fd).ok();
+    }
+}
@@ -1,11 +1,14 @@
+use crate::constants::*;
 use crate::events::*;
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
+use std::time::Instant;
 
 /// The different types of events used by this crate.
 ///
 /// Lots of other event types exist, such as toggling switches on the device, but only events
 /// thought relevant to user input were used.
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum InputEvent {
     KeyEvent(KeyEvent),
     RelAxisEvent(RelAxisEvent),
@@ -28,3 +31,71 @@ impl TryFrom<evdev::InputEvent> for InputEvent {
         Err(error)
     }
 }
+
+impl From<&InputEvent> for evdev::InputEvent {
+    fn from(ev: &InputEvent) -> Self {
+        match ev {
+            InputEvent::KeyEvent(kev) => {
+                let value = match kev.state() {
+                    PressState::Down => 1,
+                    PressState::Up => 0,
+                };
+                Self::new(
+                    evdev::EventType::KEY,
+                    evdev::Key::from(kev.key()).code(),
+                    value,
+                )
+            }
+            InputEvent::RelAxisEvent(rev) => Self::new(
+                evdev::EventType::RELATIVE,
+                evdev::RelativeAxisType::from(rev.axis()).0,
+                rev.state(),
+            ),
+            InputEvent::AbsAxisEvent(aev) => Self::new(
+                evdev::EventType::ABSOLUTE,
+                evdev::AbsoluteAxisType::from(aev.axis()).0,
+                aev.state(),
+            ),
+        }
+    }
+}
+
+/// An [InputEvent] tagged with the input device it came from.
+///
+/// The source is the index of the device within an
+/// [InputDeviceSet](crate::input::device_set::InputDeviceSet).
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SourcedInputEvent {
+    pub source: usize,
+    pub event: InputEvent,
+
+    /// Whether this is the last event of a frame, which the device ends with a SYN_REPORT.
+    ///
+    /// The events of a frame happened together, such as both axes of a stick moving.
+    #[serde(default)]
+    pub frame_end: bool,
+
+    /// When the event happened, if the source keeps its own clock, such as a recorded session
+    /// replayed faster than it was recorded. Events without a time happened as they arrive.
+    #[serde(skip)]
+    pub time: Option<Instant>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evdev_round_trip() {
+        let events = vec![
+            InputEvent::KeyEvent(KeyEvent::new(KeyCode::BTN_SOUTH, PressState::Down)),
+            InputEvent::KeyEvent(KeyEvent::new(KeyCode::BTN_SOUTH, PressState::Up)),
+            InputEvent::AbsAxisEvent(AbsAxisEvent::new(AbsAxisCode::ABS_RX, -1200)),
+            InputEvent::RelAxisEvent(RelAxisEvent::new(RelAxisCode::REL_X, 5)),
+        ];
+        for ev in events {
+            let raw: evdev::InputEvent = (&ev).into();
+            assert_eq!(InputEvent::try_from(raw), Ok(ev));
+        }
+    }
+}
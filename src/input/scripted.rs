@@ -0,0 +1,207 @@
+use super::events::*;
+use super::source::InputSource;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// An [InputEvent] along with when it happened.
+///
+/// In a file, every line holds one event, such as
+/// `{"time_ms": 120, "source": 0, "event": {"KeyEvent": {"key": "BTN_EAST", "state": "Down"}}}`.
+/// The source can be left out for single device scripts.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TimedInputEvent {
+    /// The time of the event, in milliseconds since the start of the script.
+    pub time_ms: u64,
+
+    /// The index of the device the event came from.
+    #[serde(default)]
+    pub source: usize,
+
+    pub event: InputEvent,
+}
+
+impl TimedInputEvent {
+    /// Returns the time of the event since the start of the script.
+    pub fn time(&self) -> Duration {
+        Duration::from_millis(self.time_ms)
+    }
+}
+
+/// An [InputSource] that plays back a fixed list of events.
+///
+/// Without pacing, every remaining event is produced by the first poll, which makes tests fast and
+/// deterministic. With pacing, events are produced at their original times, measured from the
+/// first poll.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::events::*;
+/// use chord2key::input::events::*;
+/// use chord2key::input::scripted::*;
+/// use chord2key::input::source::InputSource;
+/// use std::time::Duration;
+///
+/// let press = |time_ms, state| TimedInputEvent {
+///     time_ms,
+///     source: 0,
+///     event: InputEvent::KeyEvent(KeyEvent::new(KeyCode::BTN_EAST, state)),
+/// };
+/// let mut source = ScriptedSource::new(vec![
+///     press(0, PressState::Down),
+///     press(50, PressState::Up),
+/// ]);
+///
+/// let mut count = 0;
+/// source
+///     .poll_timeout(Duration::from_millis(10), &mut |_ev| count += 1)
+///     .unwrap();
+/// assert_eq!(count, 2);
+/// assert!(source.is_finished());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScriptedSource {
+    events: VecDeque<TimedInputEvent>,
+    paced: bool,
+    start: Option<Instant>,
+}
+
+impl ScriptedSource {
+    /// Creates a new unpaced source from a list of events, which must be ordered by time.
+    pub fn new(events: Vec<TimedInputEvent>) -> Self {
+        Self {
+            events: events.into(),
+            paced: false,
+            start: None,
+        }
+    }
+
+    /// Loads a source from a file with one JSON [TimedInputEvent] per line.
+    ///
+    /// Blank lines are skipped. Will return an error if the file is not readable, or if a line is
+    /// badly formatted.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut events = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line)
+                .map_err(|e| format!("Line {} is badly formatted: {}", i + 1, e))?;
+            events.push(event);
+        }
+        Ok(Self::new(events))
+    }
+
+    /// Sets whether events are produced at their original times.
+    pub fn paced(mut self, paced: bool) -> Self {
+        self.paced = paced;
+        self
+    }
+
+    /// Returns the events that haven't been produced yet.
+    pub fn remaining(&self) -> impl Iterator<Item = &TimedInputEvent> {
+        self.events.iter()
+    }
+}
+
+impl InputSource for ScriptedSource {
+    fn poll_timeout(
+        &mut self,
+        timeout: Duration,
+        callback: &mut dyn FnMut(&SourcedInputEvent),
+    ) -> Result<(), std::io::Error> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+
+        if self.paced {
+            // Wait for the next event, up to the timeout
+            if let Some(next) = self.events.front() {
+                let wait = next.time().saturating_sub(start.elapsed());
+                std::thread::sleep(wait.min(timeout));
+            }
+        }
+
+        while let Some(next) = self.events.front() {
+            if self.paced && next.time() > start.elapsed() {
+                break;
+            }
+            let next = self.events.pop_front().unwrap();
+            // Scripts don't record frames, so every event is a frame of its own
+            callback(&SourcedInputEvent {
+                source: next.source,
+                time: Some(start + next.time()),
+                event: next.event,
+                frame_end: true,
+            });
+        }
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Writes input events in the file format read by [ScriptedSource::load_from_file].
+///
+/// Event times are measured from when the writer was created.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::events::*;
+/// use chord2key::input::events::*;
+/// use chord2key::input::scripted::*;
+///
+/// let mut writer = ScriptWriter::new(Vec::new());
+/// let event = SourcedInputEvent {
+///     source: 1,
+///     event: InputEvent::KeyEvent(KeyEvent::new(KeyCode::BTN_EAST, PressState::Down)),
+///     frame_end: true,
+///     time: None,
+/// };
+/// writer.write(&event).unwrap();
+///
+/// let line = String::from_utf8(writer.into_inner()).unwrap();
+/// let timed: TimedInputEvent = serde_json::from_str(&line).unwrap();
+/// assert_eq!(timed.source, 1);
+/// assert_eq!(timed.event, event.event);
+/// ```
+pub struct ScriptWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> ScriptWriter<W> {
+    /// Creates a new writer, starting the clock for event times.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes an event as a single line, timestamped with the time since the writer was created.
+    pub fn write(&mut self, ev: &SourcedInputEvent) -> Result<(), Box<dyn Error>> {
+        let timed = TimedInputEvent {
+            time_ms: self.start.elapsed().as_millis() as u64,
+            source: ev.source,
+            event: ev.event.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &timed)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// A description of which input device to use.
+///
+/// Every field is optional, and a device is selected only if it matches all of the fields that
+/// are set. Text fields accept glob patterns, where `*` matches any run of characters and `?`
+/// matches a single character.
+///
+/// A selector can be written in a configuration file as either a plain device name or an object
+/// containing any of the fields.
+///
+/// # Example
+/// ```
+/// use chord2key::input::selector::*;
+///
+/// let by_name: DeviceSelector = serde_json::from_str(r#""Nintendo Switch Pro Controller""#).unwrap();
+/// assert_eq!(by_name.name.as_deref(), Some("Nintendo Switch Pro Controller"));
+///
+/// let by_ids: DeviceSelector =
+///     serde_json::from_str(r#"{"vendor": 1406, "product": 8201, "uniq": "AA:BB:CC:DD:EE:FF"}"#)
+///         .unwrap();
+/// assert_eq!(by_ids.name, None);
+/// assert_eq!(by_ids.vendor, Some(0x057e));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(from = "SelectorRepr")]
+pub struct DeviceSelector {
+    /// A glob pattern for the name reported by the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The bus type of the device, such as 3 for USB or 5 for Bluetooth.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus_type: Option<u16>,
+
+    /// The vendor ID of the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<u16>,
+
+    /// The product ID of the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<u16>,
+
+    /// A glob pattern for the physical location of the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phys: Option<String>,
+
+    /// A glob pattern for the unique identifier of the device, typically its MAC address.
+    ///
+    /// Matching is case insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uniq: Option<String>,
+}
+
+/// The accepted configuration file representations of a [DeviceSelector].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SelectorRepr {
+    Name(String),
+    Fields {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        bus_type: Option<u16>,
+        #[serde(default)]
+        vendor: Option<u16>,
+        #[serde(default)]
+        product: Option<u16>,
+        #[serde(default)]
+        phys: Option<String>,
+        #[serde(default)]
+        uniq: Option<String>,
+    },
+}
+
+impl From<SelectorRepr> for DeviceSelector {
+    fn from(repr: SelectorRepr) -> Self {
+        match repr {
+            SelectorRepr::Name(name) => name.into(),
+            SelectorRepr::Fields {
+                name,
+                bus_type,
+                vendor,
+                product,
+                phys,
+                uniq,
+            } => Self {
+                name,
+                bus_type,
+                vendor,
+                product,
+                phys,
+                uniq,
+            },
+        }
+    }
+}
+
+impl From<String> for DeviceSelector {
+    fn from(name: String) -> Self {
+        Self {
+            name: Some(name),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for DeviceSelector {
+    fn from(name: &str) -> Self {
+        name.to_string().into()
+    }
+}
+
+impl DeviceSelector {
+    /// Returns whether the described device matches every set field of this selector.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::input::selector::*;
+    ///
+    /// let selector = DeviceSelector {
+    ///     name: Some("Nintendo Switch * Joy-Con".to_string()),
+    ///     uniq: Some("aa:bb:*".to_string()),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let mut info = DeviceInfo {
+    ///     name: Some("Nintendo Switch Left Joy-Con".to_string()),
+    ///     uniq: Some("AA:BB:CC:DD:EE:FF".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// assert!(selector.matches(&info));
+    ///
+    /// info.uniq = Some("11:22:33:44:55:66".to_string());
+    /// assert!(!selector.matches(&info));
+    /// ```
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        fn text_matches(
+            pattern: &Option<String>,
+            text: &Option<String>,
+            ignore_case: bool,
+        ) -> bool {
+            match pattern {
+                None => true,
+                Some(pattern) => match text {
+                    None => false,
+                    Some(text) if ignore_case => {
+                        glob_match(&pattern.to_lowercase(), &text.to_lowercase())
+                    }
+                    Some(text) => glob_match(pattern, text),
+                },
+            }
+        }
+        fn id_matches(expected: Option<u16>, id: u16) -> bool {
+            expected.map_or(true, |expected| expected == id)
+        }
+
+        text_matches(&self.name, &info.name, false)
+            && id_matches(self.bus_type, info.bus_type)
+            && id_matches(self.vendor, info.vendor)
+            && id_matches(self.product, info.product)
+            && text_matches(&self.phys, &info.phys, false)
+            && text_matches(&self.uniq, &info.uniq, true)
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::<String>::new();
+        if let Some(name) = &self.name {
+            parts.push(format!("name {:?}", name));
+        }
+        if let Some(bus_type) = self.bus_type {
+            parts.push(format!("bus {:#06x}", bus_type));
+        }
+        if let Some(vendor) = self.vendor {
+            parts.push(format!("vendor {:#06x}", vendor));
+        }
+        if let Some(product) = self.product {
+            parts.push(format!("product {:#06x}", product));
+        }
+        if let Some(phys) = &self.phys {
+            parts.push(format!("phys {:?}", phys));
+        }
+        if let Some(uniq) = &self.uniq {
+            parts.push(format!("uniq {:?}", uniq));
+        }
+        if parts.is_empty() {
+            write!(f, "any device")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// The identifying information of an input device, used to match against a [DeviceSelector].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceInfo {
+    /// The device node, such as /dev/input/event3.
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub bus_type: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Reads the identifying information of an opened device.
+    pub fn from_device(path: PathBuf, device: &evdev::Device) -> Self {
+        let id = device.input_id();
+        Self {
+            path,
+            name: device.name().map(String::from),
+            bus_type: id.bus_type().0,
+            vendor: id.vendor(),
+            product: id.product(),
+            phys: device.physical_path().map(String::from),
+            uniq: device.unique_name().map(String::from),
+        }
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:?} (bus {:#06x}, vendor {:#06x}, product {:#06x}, phys {:?}, uniq {:?})",
+            self.path.display(),
+            self.name.as_deref().unwrap_or("Unnamed device"),
+            self.bus_type,
+            self.vendor,
+            self.product,
+            self.phys.as_deref().unwrap_or(""),
+            self.uniq.as_deref().unwrap_or(""),
+        )
+    }
+}
+
+/// The reasons a [DeviceSelector] could not pick a single device.
+#[derive(Clone, Debug)]
+pub enum SelectorError {
+    /// No device matched. Contains every available device.
+    NoMatch(DeviceSelector, Vec<DeviceInfo>),
+
+    /// More than one device matched. Contains every matching device.
+    Ambiguous(DeviceSelector, Vec<DeviceInfo>),
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let devices = match self {
+            Self::NoMatch(selector, devices) => {
+                write!(f, "No input device matches {}.", selector)?;
+                if devices.is_empty() {
+                    return write!(f, " No input devices are available.");
+                }
+                write!(f, " Available devices:")?;
+                devices
+            }
+            Self::Ambiguous(selector, devices) => {
+                write!(f, "Multiple input devices match {}:", selector)?;
+                devices
+            }
+        };
+        for device in devices {
+            write!(f, "\n    {}", device)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// Matches text against a glob pattern, where `*` matches any run of characters and `?` matches a
+/// single character.
+///
+/// # Example
+/// ```
+/// use chord2key::input::selector::glob_match;
+///
+/// assert!(glob_match("Nintendo*", "Nintendo Switch Pro Controller"));
+/// assert!(glob_match("usb-*/input?", "usb-0000:00:14.0-2/input0"));
+/// assert!(!glob_match("Nintendo", "Nintendo Switch Pro Controller"));
+/// ```
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+
+    // The pattern index after the last seen `*`, and the text index it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` absorb one more character
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
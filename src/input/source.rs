@@ -0,0 +1,55 @@
+use super::device::InputDevice;
+use super::events::SourcedInputEvent;
+use crate::output::rumble::{DeviceRumble, RumbleBackend};
+use std::time::Duration;
+
+/// Anything that produces input events for a [Mapper](crate::mapping::mapper::Mapper).
+///
+/// This is implemented by the real input devices, as well as by
+/// [ScriptedSource](super::scripted::ScriptedSource) so the whole pipeline can run without any
+/// hardware.
+pub trait InputSource {
+    /// Waits up to the timeout for events, sending every valid event to the callback.
+    ///
+    /// May return early without any events, such as when the process receives a signal.
+    fn poll_timeout(
+        &mut self,
+        timeout: Duration,
+        callback: &mut dyn FnMut(&SourcedInputEvent),
+    ) -> Result<(), std::io::Error>;
+
+    /// Returns whether the source will never produce another event.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// Returns the real input devices behind the source, indexed by [SourcedInputEvent::source].
+    fn devices(&self) -> &[InputDevice] {
+        &[]
+    }
+
+    /// Takes or releases an exclusive grab on the source, if it supports one.
+    fn set_grab(&mut self, _grab: bool) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    /// Uploads a rumble effect to every real input device that can rumble.
+    ///
+    /// Devices that can't rumble are skipped, as are devices the effect couldn't be uploaded to.
+    fn rumble_effects(&mut self) -> Vec<DeviceRumble> {
+        Vec::new()
+    }
+
+    /// Returns what plays rumbles on the source, which by default plays them on every effect from
+    /// [InputSource::rumble_effects].
+    fn rumble_backend(&mut self) -> Box<dyn RumbleBackend> {
+        Box::new(self.rumble_effects())
+    }
+
+    /// Waits for the source to come back after a disconnect, until `stop` returns true.
+    ///
+    /// Returns whether the source was reconnected. Sources that can't reconnect return false.
+    fn reconnect(&mut self, _stop: &mut dyn FnMut() -> bool) -> bool {
+        false
+    }
+}
@@ -8,7 +8,13 @@ extern crate num_derive;
 
 pub mod attribute_set;
 pub mod constants;
+pub mod control;
 pub mod events;
 pub mod input;
+pub mod logging;
 pub mod mapping;
+pub mod notify;
 pub mod output;
+
+#[cfg(test)]
+mod test_util;
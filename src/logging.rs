@@ -0,0 +1,137 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The environment variable read by [level_from_env].
+pub const LOG_ENV_VAR: &str = "CHORD2KEY_LOG";
+
+/// The format of each logged line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LogFormat {
+    /// Human readable lines, such as `[INFO chord2key::mapping::mapper] Switched to layer.json`.
+    Text,
+
+    /// One JSON object per line, with the `time`, `level`, `target` and `message` fields.
+    Json,
+}
+
+/// A logger that writes every enabled record to stderr.
+///
+/// The library only logs through the [log] macros, so any other logger can be used instead.
+#[derive(Copy, Clone, Debug)]
+pub struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+/// A single JSON log line.
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    /// Seconds since the Unix epoch.
+    time: f64,
+    level: &'a str,
+    target: &'a str,
+    message: String,
+}
+
+impl Logger {
+    /// Creates a logger that writes records up to the given level in the given format.
+    pub fn new(level: LevelFilter, format: LogFormat) -> Self {
+        Self { level, format }
+    }
+
+    /// Installs the logger as the global logger.
+    ///
+    /// Returns an error if a global logger was already installed.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(self.level);
+        Ok(())
+    }
+
+    /// Formats a record as a single line, without the trailing newline.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::logging::*;
+    /// use log::{Level, LevelFilter, Record};
+    ///
+    /// let record = Record::builder()
+    ///     .args(format_args!("Switched layers"))
+    ///     .level(Level::Info)
+    ///     .target("chord2key::mapping::mapper")
+    ///     .build();
+    ///
+    /// let text = Logger::new(LevelFilter::Info, LogFormat::Text);
+    /// assert_eq!(
+    ///     text.format(&record),
+    ///     "[INFO chord2key::mapping::mapper] Switched layers"
+    /// );
+    ///
+    /// let json = Logger::new(LevelFilter::Info, LogFormat::Json);
+    /// let line: serde_json::Value = serde_json::from_str(&json.format(&record)).unwrap();
+    /// assert_eq!(line["level"], "INFO");
+    /// assert_eq!(line["message"], "Switched layers");
+    /// ```
+    pub fn format(&self, record: &Record) -> String {
+        match self.format {
+            LogFormat::Text => {
+                format!("[{} {}] {}", record.level(), record.target(), record.args())
+            }
+            LogFormat::Json => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs_f64())
+                    .unwrap_or(0.0);
+                let json = JsonRecord {
+                    time,
+                    level: record.level().as_str(),
+                    target: record.target(),
+                    message: record.args().to_string(),
+                };
+                serde_json::to_string(&json).unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            // Logging must never take down the program, so write errors are ignored
+            let mut stderr = std::io::stderr().lock();
+            writeln!(stderr, "{}", self.format(record)).ok();
+        }
+    }
+
+    fn flush(&self) {
+        std::io::stderr().flush().ok();
+    }
+}
+
+/// Parses a level name, such as `info` or `TRACE`. `off` disables logging.
+///
+/// # Example
+/// ```
+/// use chord2key::logging::*;
+/// use log::LevelFilter;
+///
+/// assert_eq!(parse_level("trace"), Some(LevelFilter::Trace));
+/// assert_eq!(parse_level("Warn"), Some(LevelFilter::Warn));
+/// assert_eq!(parse_level("loud"), None);
+/// ```
+pub fn parse_level(name: &str) -> Option<LevelFilter> {
+    name.trim().parse().ok()
+}
+
+/// Returns the level set by the [LOG_ENV_VAR] environment variable, if it's set to a valid level.
+pub fn level_from_env() -> Option<LevelFilter> {
+    std::env::var(LOG_ENV_VAR)
+        .ok()
+        .and_then(|name| parse_level(&name))
+}
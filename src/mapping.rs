@@ -1,5 +1,8 @@
 pub mod actions;
 pub mod configuration;
+pub mod layout;
 pub mod mapper;
 mod maps;
+pub mod stats;
 pub mod thresholds;
+pub mod training;
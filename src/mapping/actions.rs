@@ -19,6 +19,39 @@ pub enum InnerAction {
 
     /// Switch to a new configuration given by the path.
     SwitchConfig(std::path::PathBuf),
+
+    /// Take or release the exclusive grab on the input devices.
+    ///
+    /// Releasing the grab hands the input devices back to other programs, such as games.
+    ToggleGrab,
+
+    /// Pause or resume translating input into output actions.
+    ///
+    /// While suspended, only the resume chord is listened for. See
+    /// [Configuration::resume_chord](crate::mapping::configuration::Configuration::resume_chord).
+    Suspend,
+
+    /// Release every key and mouse motion, forget all partially entered input, and return to the
+    /// configuration file that chord2key was started with.
+    ///
+    /// Used to recover when the output state gets out of sync, such as a toggled key staying down.
+    /// See also [Configuration::panic_chord](crate::mapping::configuration::Configuration::panic_chord).
+    Panic,
+
+    /// Append a digit from 0 to 9 to the count of the next chord, like the count of a vi command.
+    ///
+    /// The next chord that emits an [OutputAction], [InnerAction::RepeatLastChord] or
+    /// [InnerAction::UndoLastChord] does so as many times as the count, which is then cleared. The
+    /// count is also cleared by an unmapped chord, or if no chord follows in time. See
+    /// [Configuration::count_timeout_ms](crate::mapping::configuration::Configuration::count_timeout_ms).
+    CountDigit(u8),
+
+    /// Undo the output of the last chord that emitted any, such as by sending a backspace for each
+    /// character it typed and releasing the keys it pressed. See [OutputAction::undo].
+    ///
+    /// Repeating it undoes earlier chords, up to
+    /// [Configuration::undo_limit](crate::mapping::configuration::Configuration::undo_limit).
+    UndoLastChord,
 }
 
 impl From<OutputAction> for Action {
@@ -50,3 +83,30 @@ impl From<InnerAction> for Action {
         Self::InnerAction(ie)
     }
 }
+
+impl Action {
+    /// Returns the character typed by the action on a US keyboard layout, if it's a [Pulse] of
+    /// keys that type exactly one. See [keys_to_char](crate::output::text::keys_to_char).
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::constants::*;
+    /// use chord2key::mapping::actions::*;
+    /// use chord2key::output::actions::*;
+    ///
+    /// let action: Action = Pulse::new(Some(vec![KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_Q]), None).into();
+    /// assert_eq!(action.typed_char(), Some('Q'));
+    ///
+    /// let action: Action = Toggle::new(Some(vec![KeyCode::KEY_Q]), None).into();
+    /// assert_eq!(action.typed_char(), None);
+    /// ```
+    pub fn typed_char(&self) -> Option<char> {
+        match self {
+            Action::OutputAction(OutputAction::Pulse(Pulse {
+                keys: Some(keys),
+                axes: None,
+            })) => crate::output::text::keys_to_char(keys),
+            _ => None,
+        }
+    }
+}
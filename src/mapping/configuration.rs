@@ -2,18 +2,40 @@ use super::actions::*;
 use super::mapper::*;
 use super::thresholds::*;
 use crate::constants::*;
+use crate::input::selector::DeviceSelector;
+use crate::notify::NotifierConfig;
 use crate::output::actions::*;
+use crate::output::rumble::RumblePattern;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Configuration {
-    /// The name of the evdev input device that this configuration should apply for
+    /// The name of the evdev input device that this configuration should apply for.
+    ///
+    /// May be left out if [Configuration::device_selector] is given instead.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub device_name: String,
 
+    /// A more specific description of the main input device, used instead of the device name.
+    ///
+    /// This allows for telling apart identical devices, or finding a device whose reported name
+    /// changes between kernel drivers. Either this or [Configuration::device_name] must be given.
+    #[serde(default)]
+    pub device_selector: Option<DeviceSelector>,
+
+    /// Any other evdev input devices that should be read alongside the main one.
+    ///
+    /// Events from all devices are combined, so chords can span multiple devices. Identical
+    /// inputs reported by different devices are treated as the same input.
+    ///
+    /// Also read from the deprecated `additional_device_names`, which took plain device names.
+    #[serde(default, alias = "additional_device_names")]
+    pub additional_devices: Vec<DeviceSelector>,
+
     /// The thresholds for axis input to be considered valid. Analogous to axis dead zones.
     pub axis_thresholds: Vec<(AbsAxisCode, AxisThreshold)>,
 
@@ -31,6 +53,132 @@ pub struct Configuration {
 
     /// Mapping of absolute axes inputs to mouse actions
     pub mouse_mapping: MouseMapInput,
+
+    /// Whether to take an exclusive grab on the input devices while mapping.
+    ///
+    /// While grabbed, the raw input events only reach chord2key, so chords can't trigger unrelated
+    /// actions in games or the desktop. Only used from the configuration file that chord2key was
+    /// started with.
+    #[serde(default)]
+    pub grab_devices: bool,
+
+    /// The chord that resumes mapping while suspended by [InnerAction::Suspend].
+    ///
+    /// If not set, the chords mapped to [InnerAction::Suspend] also resume mapping.
+    #[serde(default)]
+    pub resume_chord: Option<Vec<ChordInput>>,
+
+    /// Whether to forward the raw input events to a virtual copy of each input device while
+    /// suspended.
+    ///
+    /// Combined with [Configuration::grab_devices], this lets games read the gamepad while
+    /// suspended without also seeing its input while mapping. Only used from the configuration
+    /// file that chord2key was started with.
+    #[serde(default)]
+    pub suspend_passthrough: bool,
+
+    /// A reserved chord that triggers [InnerAction::Panic] from any configuration.
+    ///
+    /// The panic chord is checked before any other mapping, even while suspended, and works in
+    /// linked configurations that don't mention it. Only used from the configuration file that
+    /// chord2key was started with.
+    #[serde(default)]
+    pub panic_chord: Option<Vec<ChordInput>>,
+
+    /// The longest time, in milliseconds, that an output key may be held down before it's
+    /// automatically released.
+    ///
+    /// This protects against runaway input, such as a key that stays down after a glitchy
+    /// disconnect. Keys latched down by a Toggle are exempt. Disabled if not set. Only used from
+    /// the configuration file that chord2key was started with.
+    #[serde(default)]
+    pub key_hold_limit_ms: Option<u64>,
+
+    /// How long a count entered with [InnerAction::CountDigit] waits for the next chord before
+    /// it's cleared, in milliseconds. 3000 by default. Only used from the configuration file that
+    /// chord2key was started with.
+    #[serde(default)]
+    pub count_timeout_ms: Option<u64>,
+
+    /// How many chords [InnerAction::UndoLastChord] remembers the output of. 16 by default. Only
+    /// used from the configuration file that chord2key was started with.
+    #[serde(default)]
+    pub undo_limit: Option<usize>,
+
+    /// A short name for this configuration, such as "Letters", used when announcing that it became
+    /// active. If not set, the file name is used.
+    #[serde(default)]
+    pub display_name: Option<String>,
+
+    /// Which player LEDs of the controllers to light while this configuration is active, starting
+    /// with player 1, such as `[true, false, false, true]`.
+    ///
+    /// If not set, the LEDs are left as they are when switching to this configuration.
+    #[serde(default)]
+    pub led_pattern: Option<Vec<bool>>,
+
+    /// The directory holding the LEDs set by [Configuration::led_pattern], which is
+    /// `/sys/class/leds` by default. Only the LEDs of the input devices are set. Only used from the
+    /// configuration file that chord2key was started with.
+    #[serde(default)]
+    pub led_root: Option<PathBuf>,
+
+    /// The rumble played on the input devices when this configuration becomes active.
+    #[serde(default)]
+    pub layer_rumble: Option<RumblePattern>,
+
+    /// What happens when a chord that isn't mapped to any action is released in this
+    /// configuration. Nothing happens by default.
+    #[serde(default)]
+    pub unmapped_chord: UnmappedChord,
+
+    /// Rumbles played when the given chords of this configuration emit their action.
+    #[serde(default)]
+    pub chord_rumble: Vec<(Vec<ChordInput>, RumblePattern)>,
+
+    /// Chords of this configuration that repeat their action while held, like a held key on a
+    /// keyboard, such as for moving the text cursor.
+    #[serde(default)]
+    pub chord_repeat: Vec<(Vec<ChordInput>, ChordRepeat)>,
+
+    /// A file to keep counting how often each mapping fires in, across sessions. A relative path
+    /// is relative to this configuration file. Disabled if not set. Only used from the
+    /// configuration file that chord2key was started with.
+    #[serde(default)]
+    pub stats_file: Option<PathBuf>,
+
+    /// Where to announce layer changes, suspends and errors, such as on stdout or as desktop
+    /// notifications. Only used from the configuration file that chord2key was started with.
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+
+    /// Mapping of absolute axes pairs to absolute pointer positions.
+    ///
+    /// Unlike the mouse mapping, the stick deflection sets the pointer position directly rather
+    /// than its velocity.
+    #[serde(default)]
+    pub abs_mouse_mapping: AbsMouseMapInput,
+}
+
+/// What a [Configuration] does when a chord without a mapping is released.
+///
+/// Either way, the chord is counted once. The inputs left held afterwards can still emit the action
+/// of a smaller chord.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum UnmappedChord {
+    /// Do nothing.
+    #[default]
+    Ignore,
+
+    /// Log a warning naming the chord.
+    Warn,
+
+    /// Rumble the input devices.
+    Rumble(RumblePattern),
+
+    /// Handle an action as if the chord was mapped to it, such as a pulse of a key that plays a
+    /// beep.
+    Action(Action),
 }
 
 impl Configuration {
@@ -40,15 +188,37 @@ impl Configuration {
         Ok(())
     }
 
+    /// Returns the selector for the main input device.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::mapping::configuration::*;
+    ///
+    /// let config = Configuration::pro_default();
+    /// assert_eq!(config.main_device(), config.device_name.as_str().into());
+    /// ```
+    pub fn main_device(&self) -> DeviceSelector {
+        self.device_selector
+            .clone()
+            .unwrap_or_else(|| self.device_name.clone().into())
+    }
+
     /// Load a configuration from a file at the specified path.
     ///
-    /// Will return an error if the file is not readable, or if the configuration is badly
-    /// formatted.
+    /// Will return an error if the file is not readable, if the configuration is badly
+    /// formatted, or if it sets neither a device name nor a device selector.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
-        let file = File::open(path)?;
+        let file = File::open(&path)?;
         let reader = BufReader::new(file);
 
-        let config = serde_json::from_reader(reader)?;
+        let config: Self = serde_json::from_reader(reader)?;
+        if config.device_name.is_empty() && config.device_selector.is_none() {
+            return Err(format!(
+                "{} sets neither device_name nor device_selector, so there's no input device",
+                path.as_ref().display()
+            )
+            .into());
+        }
 
         Ok(config)
     }
@@ -191,6 +361,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
     #[allow(non_snake_case)]
@@ -471,6 +642,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
 
@@ -620,6 +792,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
 
@@ -877,6 +1050,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
     /// Returns a default joycon configuration mapped to the keyboard
@@ -1264,6 +1438,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
 
@@ -1638,6 +1813,7 @@ impl Configuration {
             chord_mapping,
             modifier_mapping,
             mouse_mapping,
+            ..Default::default()
         }
     }
 }
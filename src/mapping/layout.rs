@@ -0,0 +1,740 @@
+use super::actions::Action;
+use super::configuration::Configuration;
+use super::mapper::ChordInput;
+use crate::constants::*;
+use crate::output::actions::{OutputAction, Pulse};
+use crate::output::text::char_to_keys;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// The hand that holds an input.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+/// An input that generated chords may use, along with how it's operated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutInput {
+    pub input: ChordInput,
+
+    /// The hand that holds the input.
+    pub hand: Hand,
+
+    /// The finger that operates the input, such as `right thumb`.
+    pub finger: String,
+
+    /// The control the input is part of, such as `stick` or `face buttons`.
+    ///
+    /// A finger can operate several inputs of the same control at once, but never two controls.
+    pub control: String,
+
+    /// The effort of using the input, on top of [LayoutCosts::input], such as for an input that is
+    /// hard to reach.
+    #[serde(default)]
+    pub effort: f64,
+}
+
+/// How much effort each property of a chord takes. The generated layout minimizes their sum,
+/// weighted by how often each chord is entered.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutCosts {
+    /// The effort of every input of a chord, which favours smaller chords.
+    pub input: f64,
+
+    /// The effort of every input beyond the first operated by the same finger, such as pressing
+    /// two face buttons with one thumb.
+    pub finger_conflict: f64,
+
+    /// The effort of a chord that needs both hands.
+    pub cross_hand: f64,
+
+    /// The effort of a finger moving to different inputs between two consecutive chords.
+    pub finger_travel: f64,
+}
+
+impl Default for LayoutCosts {
+    fn default() -> Self {
+        Self {
+            input: 1.0,
+            finger_conflict: 1.0,
+            cross_hand: 0.5,
+            finger_travel: 0.5,
+        }
+    }
+}
+
+/// What a generated layout may use, and how the effort of its chords is measured.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutSpec {
+    /// Every input that chords may be made of.
+    pub inputs: Vec<LayoutInput>,
+
+    /// Keys to give a chord even if the corpus never types them, such as every letter.
+    #[serde(default)]
+    pub keys: Vec<KeyCode>,
+
+    /// The most inputs a chord may have.
+    #[serde(default = "LayoutSpec::default_max_chord_size")]
+    pub max_chord_size: usize,
+
+    #[serde(default)]
+    pub costs: LayoutCosts,
+}
+
+impl LayoutSpec {
+    fn default_max_chord_size() -> usize {
+        3
+    }
+
+    /// Load a layout specification from a JSON file.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// How often every key, and every pair of consecutive keys, is typed in a body of text.
+///
+/// Characters that need shift count towards their key, since shift comes from a modifier.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::mapping::layout::*;
+///
+/// let mut corpus = Corpus::default();
+/// corpus.add_text("Tee time!");
+/// assert_eq!(corpus.count(KeyCode::KEY_E), 3);
+/// assert_eq!(corpus.count(KeyCode::KEY_1), 1);
+/// assert_eq!(corpus.total(), 9);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Corpus {
+    keys: HashMap<KeyCode, u64>,
+    pairs: HashMap<(KeyCode, KeyCode), u64>,
+    total: u64,
+}
+
+impl Corpus {
+    /// Counts the keys that type the text. Characters without a key are skipped.
+    pub fn add_text(&mut self, text: &str) {
+        let mut previous = None;
+        for c in text.chars() {
+            let key = char_to_keys(c).and_then(|keys| keys.last().copied());
+            if let Some(key) = key {
+                *self.keys.entry(key).or_default() += 1;
+                self.total += 1;
+                if let Some(previous) = previous {
+                    *self.pairs.entry((previous, key)).or_default() += 1;
+                }
+            }
+            previous = key;
+        }
+    }
+
+    /// Returns how many times the key is typed.
+    pub fn count(&self, key: KeyCode) -> u64 {
+        self.keys.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Returns how many keys are typed in total.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+/// The chord that types each key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    pub chords: Vec<(KeyCode, Vec<ChordInput>)>,
+}
+
+/// Returns the key pulsed by an action, if it pulses exactly one key.
+fn pulsed_key(action: &Action) -> Option<KeyCode> {
+    match action {
+        Action::OutputAction(OutputAction::Pulse(Pulse {
+            keys: Some(keys),
+            axes: None,
+        })) if keys.len() == 1 => Some(keys[0]),
+        _ => None,
+    }
+}
+
+/// Generates the chords typing every key of a corpus, so that the most frequent keys take the
+/// least effort.
+///
+/// The chords are first handed out from the least effort to the most frequent keys, then pairs of
+/// keys are swapped, and keys moved to unused chords, as long as the expected effort decreases.
+/// The result is always the same for the same inputs.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::mapping::layout::*;
+/// use chord2key::mapping::mapper::*;
+///
+/// let button = |key, finger: &str| LayoutInput {
+///     input: ChordInput::Key(key),
+///     hand: Hand::Right,
+///     finger: finger.to_string(),
+///     control: "buttons".to_string(),
+///     effort: 0.0,
+/// };
+/// let spec = LayoutSpec {
+///     inputs: vec![
+///         button(KeyCode::BTN_SOUTH, "thumb"),
+///         button(KeyCode::BTN_TR, "index"),
+///     ],
+///     keys: vec![KeyCode::KEY_Q],
+///     max_chord_size: 2,
+///     costs: LayoutCosts::default(),
+/// };
+/// let mut corpus = Corpus::default();
+/// corpus.add_text("eeeet");
+///
+/// let optimizer = LayoutOptimizer::new(&spec, &corpus);
+/// let layout = optimizer.optimize(&[]).unwrap();
+///
+/// // The rarest key needs both buttons
+/// let chord = |key| &layout.chords.iter().find(|(k, _)| *k == key).unwrap().1;
+/// assert_eq!(chord(KeyCode::KEY_E).len(), 1);
+/// assert_eq!(chord(KeyCode::KEY_T).len(), 1);
+/// assert_eq!(chord(KeyCode::KEY_Q).len(), 2);
+/// assert!(optimizer.effort(&layout).is_some());
+/// ```
+pub struct LayoutOptimizer<'a> {
+    spec: &'a LayoutSpec,
+
+    /// The finger of every input of the specification, by index.
+    fingers: Vec<usize>,
+
+    /// Every key to assign, from the most to the least frequent.
+    keys: Vec<KeyCode>,
+
+    /// How often each key is typed, as a fraction of every key typed.
+    key_weights: Vec<f64>,
+
+    /// How often each pair of keys is typed one after the other, by index into the keys.
+    pairs: Vec<(usize, usize, f64)>,
+}
+
+impl<'a> LayoutOptimizer<'a> {
+    /// How many times every swap is tried at most.
+    const MAX_PASSES: usize = 100;
+
+    /// Prepares to generate a layout for the specification and corpus.
+    pub fn new(spec: &'a LayoutSpec, corpus: &Corpus) -> Self {
+        let mut finger_ids = HashMap::new();
+        let fingers = spec
+            .inputs
+            .iter()
+            .map(|input| {
+                let next = finger_ids.len();
+                *finger_ids.entry(input.finger.as_str()).or_insert(next)
+            })
+            .collect();
+
+        let mut keys: Vec<KeyCode> = corpus.keys.keys().copied().collect();
+        keys.extend(spec.keys.iter().filter(|key| corpus.count(**key) == 0));
+        keys.sort();
+        keys.dedup();
+        // Stable, so keys of the same frequency stay in code order
+        keys.sort_by_key(|key| std::cmp::Reverse(corpus.count(*key)));
+
+        let total = corpus.total().max(1) as f64;
+        let key_weights = keys
+            .iter()
+            .map(|key| corpus.count(*key) as f64 / total)
+            .collect();
+        let index: HashMap<KeyCode, usize> =
+            keys.iter().enumerate().map(|(i, key)| (*key, i)).collect();
+        let mut pairs: Vec<(usize, usize, f64)> = corpus
+            .pairs
+            .iter()
+            .map(|((a, b), count)| (index[a], index[b], *count as f64 / total))
+            .collect();
+        pairs.sort_by_key(|(a, b, _)| (*a, *b));
+
+        Self {
+            spec,
+            fingers,
+            keys,
+            key_weights,
+            pairs,
+        }
+    }
+
+    /// Returns every key that gets a chord, from the most to the least frequent.
+    pub fn keys(&self) -> &[KeyCode] {
+        &self.keys
+    }
+
+    /// Returns the indices of the chord's inputs in the specification, in order, if it only uses
+    /// inputs of the specification.
+    fn chord_indices(&self, chord: &[ChordInput]) -> Option<Vec<usize>> {
+        let mut indices = chord
+            .iter()
+            .map(|input| self.spec.inputs.iter().position(|i| i.input == *input))
+            .collect::<Option<Vec<usize>>>()?;
+        indices.sort_unstable();
+        indices.dedup();
+        Some(indices)
+    }
+
+    /// Returns whether the chord can be entered at all.
+    fn is_valid(&self, chord: &[usize]) -> bool {
+        let inputs = &self.spec.inputs;
+        chord.iter().enumerate().all(|(n, a)| {
+            chord[n + 1..].iter().all(|b| {
+                let (a, b) = (&inputs[*a], &inputs[*b]);
+                let opposite = match (a.input, b.input) {
+                    (ChordInput::ThresholdedAxis(a), ChordInput::ThresholdedAxis(b)) => {
+                        a.code() == b.code()
+                    }
+                    _ => false,
+                };
+                let other_control = a.finger == b.finger && a.control != b.control;
+                !opposite && !other_control
+            })
+        })
+    }
+
+    /// Returns the effort of entering the chord once.
+    fn chord_effort(&self, chord: &[usize]) -> f64 {
+        let costs = &self.spec.costs;
+        let inputs = &self.spec.inputs;
+
+        let mut effort: f64 = chord.iter().map(|i| costs.input + inputs[*i].effort).sum();
+
+        let mut fingers: Vec<usize> = chord.iter().map(|i| self.fingers[*i]).collect();
+        fingers.sort_unstable();
+        fingers.dedup();
+        effort += costs.finger_conflict * (chord.len() - fingers.len()) as f64;
+
+        let left = chord.iter().any(|i| inputs[*i].hand == Hand::Left);
+        let right = chord.iter().any(|i| inputs[*i].hand == Hand::Right);
+        if left && right {
+            effort += costs.cross_hand;
+        }
+        effort
+    }
+
+    /// Returns the effort of entering one chord right after another.
+    fn travel(&self, from: &[usize], to: &[usize]) -> f64 {
+        let uses = |chord: &[usize], finger: usize| -> Vec<usize> {
+            chord
+                .iter()
+                .copied()
+                .filter(|i| self.fingers[*i] == finger)
+                .collect()
+        };
+        let mut fingers: Vec<usize> = from.iter().map(|i| self.fingers[*i]).collect();
+        fingers.sort_unstable();
+        fingers.dedup();
+        let moved = fingers
+            .into_iter()
+            .filter(|finger| {
+                let to_inputs = uses(to, *finger);
+                !to_inputs.is_empty() && uses(from, *finger) != to_inputs
+            })
+            .count();
+        self.spec.costs.finger_travel * moved as f64
+    }
+
+    /// Returns the expected effort per key typed, with the chord of each key by index.
+    fn total(&self, chords: &[&[usize]], efforts: &[f64]) -> f64 {
+        let single: f64 = self
+            .key_weights
+            .iter()
+            .zip(efforts)
+            .map(|(weight, effort)| weight * effort)
+            .sum();
+        let travel: f64 = self
+            .pairs
+            .iter()
+            .map(|(a, b, weight)| weight * self.travel(chords[*a], chords[*b]))
+            .sum();
+        single + travel
+    }
+
+    /// Returns the expected effort per key typed in the corpus with the layout, if it gives every
+    /// key a chord the specification allows.
+    pub fn effort(&self, layout: &Layout) -> Option<f64> {
+        let chords = self
+            .keys
+            .iter()
+            .map(|key| {
+                let (_, chord) = layout.chords.iter().find(|(k, _)| k == key)?;
+                self.chord_indices(chord)
+                    .filter(|chord| self.is_valid(chord))
+            })
+            .collect::<Option<Vec<Vec<usize>>>>()?;
+        let efforts: Vec<f64> = chords.iter().map(|c| self.chord_effort(c)).collect();
+        let chords: Vec<&[usize]> = chords.iter().map(Vec::as_slice).collect();
+        Some(self.total(&chords, &efforts))
+    }
+
+    /// Returns every chord the specification allows, except the reserved ones, from the least to
+    /// the most effort.
+    fn candidates(&self, reserved: &[Vec<ChordInput>]) -> Vec<Vec<usize>> {
+        let reserved: Vec<Vec<usize>> = reserved
+            .iter()
+            .filter_map(|chord| self.chord_indices(chord))
+            .collect();
+
+        let mut candidates = Vec::new();
+        let mut stack: Vec<Vec<usize>> = vec![Vec::new()];
+        while let Some(chord) = stack.pop() {
+            let next = chord.last().map_or(0, |last| last + 1);
+            for i in next..self.spec.inputs.len() {
+                let mut bigger = chord.clone();
+                bigger.push(i);
+                if !self.is_valid(&bigger) {
+                    continue;
+                }
+                if bigger.len() < self.spec.max_chord_size {
+                    stack.push(bigger.clone());
+                }
+                if !reserved.contains(&bigger) {
+                    candidates.push(bigger);
+                }
+            }
+        }
+        // Ties are broken by inputs, so the result doesn't depend on the search order
+        candidates.sort_by(|a, b| {
+            self.chord_effort(a)
+                .total_cmp(&self.chord_effort(b))
+                .then(a.len().cmp(&b.len()))
+                .then(a.cmp(b))
+        });
+        candidates
+    }
+
+    /// Generates the layout with the least expected effort that it can find, without using any of
+    /// the reserved chords.
+    ///
+    /// Returns an error if the specification allows fewer chords than there are keys.
+    pub fn optimize(&self, reserved: &[Vec<ChordInput>]) -> Result<Layout, Box<dyn Error>> {
+        let candidates = self.candidates(reserved);
+        let n = self.keys.len();
+        if candidates.len() < n {
+            return Err(format!(
+                "Only {} chords are available for {} keys",
+                candidates.len(),
+                n
+            )
+            .into());
+        }
+        // Chords far down the list are never worth trying
+        let candidates = &candidates[..candidates.len().min(2 * n)];
+        let efforts: Vec<f64> = candidates.iter().map(|c| self.chord_effort(c)).collect();
+
+        // The keys are ordered by frequency, and the candidates by effort
+        let mut assigned: Vec<usize> = (0..n).collect();
+        let mut used = vec![false; candidates.len()];
+        used[..n].iter_mut().for_each(|used| *used = true);
+
+        let total = |assigned: &[usize]| {
+            let chords: Vec<&[usize]> =
+                assigned.iter().map(|c| candidates[*c].as_slice()).collect();
+            let key_efforts: Vec<f64> = assigned.iter().map(|c| efforts[*c]).collect();
+            self.total(&chords, &key_efforts)
+        };
+        let mut best = total(&assigned);
+
+        for _ in 0..Self::MAX_PASSES {
+            let mut improved = false;
+            for a in 0..n {
+                for b in a + 1..n {
+                    assigned.swap(a, b);
+                    let effort = total(&assigned);
+                    if effort < best - f64::EPSILON {
+                        best = effort;
+                        improved = true;
+                    } else {
+                        assigned.swap(a, b);
+                    }
+                }
+                for c in 0..candidates.len() {
+                    if used[c] {
+                        continue;
+                    }
+                    let previous = std::mem::replace(&mut assigned[a], c);
+                    let effort = total(&assigned);
+                    if effort < best - f64::EPSILON {
+                        best = effort;
+                        improved = true;
+                        used[previous] = false;
+                        used[c] = true;
+                    } else {
+                        assigned[a] = previous;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        let chords = self
+            .keys
+            .iter()
+            .zip(&assigned)
+            .map(|(key, c)| {
+                let inputs = candidates[*c]
+                    .iter()
+                    .map(|i| self.spec.inputs[*i].input)
+                    .collect();
+                (*key, inputs)
+            })
+            .collect();
+        Ok(Layout { chords })
+    }
+
+    /// Returns the chords of the configuration that pulse one of the keys, preferring the fewest
+    /// inputs if several pulse the same key.
+    pub fn layout_of(&self, config: &Configuration) -> Layout {
+        let mut layout = Layout::default();
+        for (chord, action) in &config.chord_mapping {
+            let key = match pulsed_key(action) {
+                Some(key) if self.keys.contains(&key) => key,
+                _ => continue,
+            };
+            match layout.chords.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, existing)) if existing.len() <= chord.len() => (),
+                Some((_, existing)) => *existing = chord.clone(),
+                None => layout.chords.push((key, chord.clone())),
+            }
+        }
+        layout
+    }
+
+    /// Generates a configuration layer from the base configuration.
+    ///
+    /// The chords of the base that pulse one of the keys are replaced by the generated ones. Its
+    /// other chords, and its resume and panic chords, are kept and never generated. Everything
+    /// else, such as the device and the modifiers, is taken from the base as is.
+    ///
+    /// Returns an error if the base has no threshold for an axis of the specification, or if there
+    /// aren't enough chords for every key.
+    pub fn generate(&self, base: Configuration) -> Result<Configuration, Box<dyn Error>> {
+        for input in &self.spec.inputs {
+            if let ChordInput::ThresholdedAxis(axis) = input.input {
+                let has_threshold = base
+                    .axis_thresholds
+                    .iter()
+                    .any(|(code, t)| *code == axis.code() && t.dir == axis.threshold());
+                if !has_threshold {
+                    return Err(format!("The base has no threshold for {}", input.input).into());
+                }
+            }
+        }
+
+        let mut layer = base;
+        layer
+            .chord_mapping
+            .retain(|(_, action)| !pulsed_key(action).is_some_and(|key| self.keys.contains(&key)));
+        let mut reserved: Vec<Vec<ChordInput>> = layer
+            .chord_mapping
+            .iter()
+            .map(|(chord, _)| chord.clone())
+            .collect();
+        reserved.extend(layer.resume_chord.clone());
+        reserved.extend(layer.panic_chord.clone());
+
+        let layout = self.optimize(&reserved)?;
+        for (key, chord) in layout.chords {
+            for input in &chord {
+                if !layer.chord_inputs.contains(input) {
+                    layer.chord_inputs.push(*input);
+                }
+            }
+            let pulse = Pulse::new(Some(vec![key]), None);
+            layer.chord_mapping.push((chord, pulse.into()));
+        }
+        Ok(layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::actions::InnerAction;
+    use crate::mapping::thresholds::*;
+
+    fn input(input: ChordInput, hand: Hand, finger: &str, control: &str) -> LayoutInput {
+        LayoutInput {
+            input,
+            hand,
+            finger: finger.to_string(),
+            control: control.to_string(),
+            effort: 0.0,
+        }
+    }
+
+    fn axis(code: AbsAxisCode, threshold: ThresholdType) -> ChordInput {
+        ChordInput::ThresholdedAxis(ThresholdedAxis::new(code, threshold))
+    }
+
+    fn spec() -> LayoutSpec {
+        LayoutSpec {
+            inputs: vec![
+                input(
+                    axis(AbsAxisCode::ABS_HAT0X, ThresholdType::Greater),
+                    Hand::Left,
+                    "left thumb",
+                    "dpad",
+                ),
+                input(
+                    axis(AbsAxisCode::ABS_HAT0X, ThresholdType::Lesser),
+                    Hand::Left,
+                    "left thumb",
+                    "dpad",
+                ),
+                input(
+                    ChordInput::Key(KeyCode::BTN_SOUTH),
+                    Hand::Right,
+                    "right thumb",
+                    "face",
+                ),
+                input(
+                    ChordInput::Key(KeyCode::BTN_EAST),
+                    Hand::Right,
+                    "right thumb",
+                    "face",
+                ),
+                input(
+                    axis(AbsAxisCode::ABS_RX, ThresholdType::Greater),
+                    Hand::Right,
+                    "right thumb",
+                    "stick",
+                ),
+            ],
+            keys: Vec::new(),
+            max_chord_size: 3,
+            costs: LayoutCosts::default(),
+        }
+    }
+
+    #[test]
+    fn impossible_and_reserved_chords_are_never_generated() {
+        let spec = spec();
+        let mut corpus = Corpus::default();
+        corpus.add_text("the quick brown fox");
+        let optimizer = LayoutOptimizer::new(&spec, &corpus);
+
+        let reserved = vec![ChordInput::Key(KeyCode::BTN_SOUTH)];
+        let candidates = optimizer.candidates(&[reserved]);
+        // Both directions of the hat, or the stick with a face button, can't be entered at once
+        assert!(candidates.iter().all(|c| !c.starts_with(&[0, 1])));
+        assert!(candidates
+            .iter()
+            .all(|c| !(c.contains(&4) && (c.contains(&2) || c.contains(&3)))));
+        assert!(!candidates.contains(&vec![2]));
+        // Single inputs come first
+        assert_eq!(candidates[..4], [vec![0], vec![1], vec![3], vec![4]]);
+
+        // The other single inputs, the pairs across the hands, the face buttons together, and
+        // the face buttons together with either hat direction
+        let expected = 4 + 2 * 3 + 1 + 2;
+        assert_eq!(candidates.len(), expected);
+        assert!(optimizer.optimize(&[]).is_err());
+    }
+
+    #[test]
+    fn frequent_keys_get_the_easiest_chords() {
+        let spec = spec();
+        let mut corpus = Corpus::default();
+        corpus.add_text("aaaaaaaaaabbbbbc");
+        let optimizer = LayoutOptimizer::new(&spec, &corpus);
+        assert_eq!(
+            optimizer.keys(),
+            [KeyCode::KEY_A, KeyCode::KEY_B, KeyCode::KEY_C]
+        );
+
+        let layout = optimizer.optimize(&[]).unwrap();
+        assert!(layout.chords.iter().all(|(_, chord)| chord.len() == 1));
+
+        // Repeating the same chord never travels, so the optimum is at least as good as handing
+        // out the chords by frequency alone
+        let greedy = Layout {
+            chords: vec![
+                (KeyCode::KEY_A, vec![spec.inputs[0].input]),
+                (KeyCode::KEY_B, vec![spec.inputs[1].input]),
+                (KeyCode::KEY_C, vec![spec.inputs[2].input]),
+            ],
+        };
+        assert!(optimizer.effort(&layout).unwrap() <= optimizer.effort(&greedy).unwrap());
+    }
+
+    #[test]
+    fn generated_layers_keep_the_other_chords_of_the_base() {
+        let spec = spec();
+        let mut corpus = Corpus::default();
+        corpus.add_text("ab");
+
+        let switch = vec![ChordInput::Key(KeyCode::BTN_EAST)];
+        let base = Configuration {
+            axis_thresholds: vec![
+                (
+                    AbsAxisCode::ABS_HAT0X,
+                    AxisThreshold {
+                        dir: ThresholdType::Greater,
+                        threshold: 1,
+                    },
+                ),
+                (
+                    AbsAxisCode::ABS_HAT0X,
+                    AxisThreshold {
+                        dir: ThresholdType::Lesser,
+                        threshold: -1,
+                    },
+                ),
+                (
+                    AbsAxisCode::ABS_RX,
+                    AxisThreshold {
+                        dir: ThresholdType::Greater,
+                        threshold: 16000,
+                    },
+                ),
+            ],
+            chord_mapping: vec![
+                (
+                    vec![ChordInput::Key(KeyCode::BTN_SOUTH)],
+                    Pulse::new(Some(vec![KeyCode::KEY_A]), None).into(),
+                ),
+                (
+                    switch.clone(),
+                    InnerAction::SwitchConfig("symbols.json".into()).into(),
+                ),
+            ],
+            ..Configuration::default()
+        };
+
+        let optimizer = LayoutOptimizer::new(&spec, &corpus);
+        assert!(optimizer.effort(&optimizer.layout_of(&base)).is_none());
+
+        let layer = optimizer.generate(base).unwrap();
+        assert_eq!(layer.chord_mapping.len(), 3);
+        assert!(layer.chord_mapping.contains(&(
+            switch.clone(),
+            InnerAction::SwitchConfig("symbols.json".into()).into()
+        )));
+        let layout = optimizer.layout_of(&layer);
+        assert_eq!(layout.chords.len(), 2);
+        assert!(layout.chords.iter().all(|(_, chord)| *chord != switch));
+        assert!(optimizer.effort(&layout).is_some());
+
+        let mut missing = spec.clone();
+        missing.inputs[4].input = axis(AbsAxisCode::ABS_RY, ThresholdType::Greater);
+        let optimizer = LayoutOptimizer::new(&missing, &corpus);
+        assert!(optimizer.generate(Configuration::default()).is_err());
+    }
+}
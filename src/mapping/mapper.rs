@@ -1,20 +1,31 @@
 use super::actions::*;
 use super::configuration::*;
+use super::maps::abs_mouse_map::AbsMouseMap;
 use super::maps::chord_map::ChordMap;
 use super::maps::modifier_map::ModifierMap;
 use super::maps::mouse_map::MouseMap;
+use super::stats::UsageStats;
 use super::thresholds::*;
 use crate::constants::*;
-use crate::input::events::InputEvent;
+use crate::input::events::{InputEvent, SourcedInputEvent};
+use crate::input::selector::DeviceSelector;
+use crate::input::source::InputSource;
+use crate::notify::{Notifier, NotifierConfig};
 use crate::output::actions::*;
 use crate::output::device::*;
+use crate::output::leds::{LedBackend, SysfsLeds};
+use crate::output::rumble::{RumbleBackend, RumblePattern};
 use crate::strum::IntoEnumIterator;
-use std::collections::HashMap;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 // Publicly export the data types used to create the internal mappers
-pub use super::maps::chord_map::{ChordInput, ChordMapInput};
+pub use super::maps::abs_mouse_map::{AbsMouseInput, AbsMouseMapInput, AbsMouseRegion};
+pub use super::maps::chord_map::{ChordInput, ChordMapInput, ChordRepeat};
 pub use super::maps::modifier_map::{ModifierInput, ModifierMapInput};
 pub use super::maps::mouse_map::{MouseInput, MouseMapInput, MouseProfile};
 
@@ -22,28 +33,188 @@ struct Maps {
     pub chords: ChordMap,
     pub modifiers: ModifierMap,
     pub mouse: MouseMap,
+    pub abs_mouse: AbsMouseMap,
+    pub resume: ChordMap,
 }
 
+/// The rumbles configured for a single configuration file.
+struct LayerRumble {
+    activated: Option<RumblePattern>,
+    chords: Vec<(Vec<ChordInput>, RumblePattern)>,
+}
+
+impl LayerRumble {
+    fn is_empty(&self) -> bool {
+        self.activated.is_none() && self.chords.is_empty()
+    }
+
+    /// Returns the rumble for the chord, which may have its inputs in any order.
+    fn for_chord(&self, chord: &[ChordInput]) -> Option<RumblePattern> {
+        self.chords
+            .iter()
+            .find(|(inputs, _)| {
+                inputs.len() == chord.len() && inputs.iter().all(|input| chord.contains(input))
+            })
+            .map(|(_, pattern)| *pattern)
+    }
+}
+
+/// What caused the [Mapper] to resolve an [Action].
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ActionOrigin {
+    /// A chord, given as all of its inputs.
+    Chord(Vec<ChordInput>),
+
+    /// A modifier, given as the input event that changed its state.
+    Modifier(InputEvent),
+
+    /// An axis mouse mapping, given as the input event that changed its state.
+    Mouse(InputEvent),
+
+    /// A chord without a mapping, given as all of its inputs. See [UnmappedChord::Action].
+    UnmappedChord(Vec<ChordInput>),
+
+    /// An action injected from outside, such as through the
+    /// [control socket](crate::control::ControlServer).
+    Injected,
+}
+
+impl std::fmt::Display for ActionOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |inputs: &[ChordInput]| {
+            let inputs: Vec<String> = inputs.iter().map(|i| format!("{:?}", i)).collect();
+            inputs.join(" + ")
+        };
+        match self {
+            Self::Chord(inputs) => write!(f, "Chord {}", join(inputs)),
+            Self::UnmappedChord(inputs) => write!(f, "Unmapped chord {}", join(inputs)),
+            Self::Modifier(ev) => write!(f, "Modifier {:?}", ev),
+            Self::Mouse(ev) => write!(f, "Mouse {:?}", ev),
+            Self::Injected => write!(f, "Injected"),
+        }
+    }
+}
+
+/// Something that happened inside a [Mapper], reported to its observers and notifiers.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum MapperEvent {
+    /// An action was resolved from input, and is about to be handled.
+    ActionResolved {
+        action: Action,
+        origin: ActionOrigin,
+        /// The configuration file that was active when the action was resolved.
+        layer: PathBuf,
+    },
+
+    /// A chord without a mapping was released. See [Configuration::unmapped_chord].
+    ChordUnmapped {
+        chord: Vec<ChordInput>,
+        /// The configuration file that was active when the chord was released.
+        layer: PathBuf,
+    },
+
+    /// A configuration file became active, such as through [InnerAction::SwitchConfig].
+    LayerChanged {
+        layer: PathBuf,
+        /// The display name of the configuration, or its file name if it has none.
+        name: String,
+    },
+
+    /// Mapping was suspended or resumed.
+    SuspendChanged { suspended: bool },
+
+    /// Something went wrong, such as the output device stopping.
+    Error { message: String },
+}
+
+/// A callback that is given every [MapperEvent].
+pub type MapperObserver = Box<dyn FnMut(&MapperEvent)>;
+
 /// Converts an [InputEvent] to the expected [Action] and emits it using its stored [OutputDevice].
 pub struct Mapper {
     output_device: OutputDevice,
-    input_device_name: String,
+    input_devices: Vec<DeviceSelector>,
     current_config_index: usize,
     mappings_vec: Vec<Maps>,
     paths_to_indices: HashMap<Option<PathBuf>, usize>,
+    layer_paths: Vec<PathBuf>,
+    layer_names: Vec<String>,
+    led_patterns: Vec<Option<Vec<bool>>>,
+    led_root: PathBuf,
+    leds: Option<Box<dyn LedBackend>>,
+    layer_rumbles: Vec<LayerRumble>,
+    rumble: Option<Box<dyn RumbleBackend>>,
+    unmapped_chord_actions: Vec<UnmappedChord>,
+    unmapped_chord_count: u64,
+    stats_path: Option<PathBuf>,
+    stats: Option<UsageStats>,
+    observers: Vec<MapperObserver>,
+    notifier_configs: Vec<NotifierConfig>,
+
+    /// The notifiers built by [Mapper::enable_notifiers], or None while they're disabled.
+    notifiers: Option<Vec<Box<dyn Notifier>>>,
+    grab_devices: bool,
+    suspend_passthrough: bool,
+    suspended: bool,
+    panic_mapping: Option<ChordMap>,
+
+    /// The count entered for the next chord, along with when it's cleared if no chord follows.
+    count: Option<(u32, Instant)>,
+    count_timeout: Duration,
+
+    /// The input devices holding each key down, by [SourcedInputEvent::source].
+    held_keys: HashMap<KeyCode, HashSet<usize>>,
+
+    /// The time of the input or tick being handled. See [Mapper::handle_event_at].
+    now: Instant,
+
+    /// The chords that emitted output, and that output, oldest first. See
+    /// [InnerAction::UndoLastChord].
+    undo_history: VecDeque<(Vec<ChordInput>, Vec<OutputAction>)>,
+    undo_limit: usize,
+
+    /// The output of the chord being handled, while it's handled.
+    chord_output: Option<Vec<OutputAction>>,
 }
 
 impl Mapper {
+    /// How long a count waits for the next chord if [Configuration::count_timeout_ms] isn't set.
+    const DEFAULT_COUNT_TIMEOUT: Duration = Duration::from_millis(3000);
+
+    /// The largest count, so a mistyped count can't emit an action endlessly.
+    const MAX_COUNT: u32 = 999;
+
+    /// How many chords can be undone if [Configuration::undo_limit] isn't set.
+    const DEFAULT_UNDO_LIMIT: usize = 16;
+
     fn get_mappings(config: Configuration) -> Maps {
         let thresholds = AllAxisThresholds::init(config.axis_thresholds);
-        let chord_mapping = ChordMap::init(config.chord_mapping, thresholds.clone());
+
+        // Without a dedicated resume chord, the chords that suspend also resume
+        let suspend: Action = InnerAction::Suspend.into();
+        let resume_mapping: ChordMapInput = match config.resume_chord {
+            Some(chord) => vec![(chord, suspend)],
+            None => config
+                .chord_mapping
+                .iter()
+                .filter(|(_chord, action)| *action == suspend)
+                .cloned()
+                .collect(),
+        };
+        let resume_mapping = ChordMap::init(resume_mapping, thresholds.clone());
+
+        let mut chord_mapping = ChordMap::init(config.chord_mapping, thresholds.clone());
+        chord_mapping.set_repeats(config.chord_repeat);
         let modifier_mapping = ModifierMap::init(config.modifier_mapping, thresholds.clone());
         let mouse_mapping = MouseMap::init(config.mouse_mapping, thresholds);
+        let abs_mouse_mapping = AbsMouseMap::init(config.abs_mouse_mapping);
 
         Maps {
             chords: chord_mapping,
             modifiers: modifier_mapping,
             mouse: mouse_mapping,
+            abs_mouse: abs_mouse_mapping,
+            resume: resume_mapping,
         }
     }
 
@@ -53,10 +224,25 @@ impl Mapper {
         path: P,
     ) -> Result<Self, Box<dyn Error>> {
         let pathbuf = path.as_ref().to_path_buf().canonicalize()?;
-        let mut device_name: Option<String> = None;
+        let mut input_devices = Vec::<DeviceSelector>::new();
         let mut paths_to_indices = HashMap::<Option<PathBuf>, usize>::new();
+        let mut layer_paths = Vec::<PathBuf>::new();
+        let mut layer_names = Vec::<String>::new();
+        let mut led_patterns = Vec::<Option<Vec<bool>>>::new();
+        let mut led_root: Option<PathBuf> = None;
+        let mut layer_rumbles = Vec::<LayerRumble>::new();
+        let mut unmapped_chord_actions = Vec::<UnmappedChord>::new();
+        let mut stats_path: Option<PathBuf> = None;
+        let mut notifier_configs = Vec::<NotifierConfig>::new();
         let mut mappings_vec = Vec::<Maps>::new();
         let mut config_paths: Vec<PathBuf> = vec![pathbuf];
+        let mut uses_abs_pointer = false;
+        let mut grab_devices = false;
+        let mut suspend_passthrough = false;
+        let mut panic_mapping: Option<ChordMap> = None;
+        let mut key_hold_limit: Option<Duration> = None;
+        let mut count_timeout = Self::DEFAULT_COUNT_TIMEOUT;
+        let mut undo_limit = Self::DEFAULT_UNDO_LIMIT;
 
         let mut i = 0;
 
@@ -70,27 +256,63 @@ impl Mapper {
 
             // Load the configuration file
             let config = Configuration::load_from_file(&config_paths[i])?;
+            debug!("Loaded {}", config_paths[i].display());
 
-            // Check for same device name
-            match &mut device_name {
-                Some(name) => {
-                    if name != &config.device_name {
-                        println!("{} != {}", name, &config.device_name);
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!(
-                                "Device in configuration file {:?} does not match \
-                                            device in source file",
-                                &config_paths[i]
-                            ),
-                        )));
+            // Collect every unique input device, keeping the main device of the source file first
+            std::iter::once(config.main_device())
+                .chain(config.additional_devices.iter().cloned())
+                .for_each(|selector| {
+                    if !input_devices.contains(&selector) {
+                        input_devices.push(selector);
                     }
+                });
+
+            // Device-wide settings come from the source file
+            if i == 0 {
+                grab_devices = config.grab_devices;
+                suspend_passthrough = config.suspend_passthrough;
+                key_hold_limit = config.key_hold_limit_ms.map(Duration::from_millis);
+                if let Some(timeout) = config.count_timeout_ms {
+                    count_timeout = Duration::from_millis(timeout);
                 }
-                None => {
-                    device_name = Some(config.device_name.clone());
-                }
+                undo_limit = config.undo_limit.unwrap_or(undo_limit);
+                notifier_configs = config.notifiers.clone();
+                led_root = config.led_root.clone();
+                stats_path = match &config.stats_file {
+                    Some(file) => Some(
+                        config_paths[i]
+                            .parent()
+                            .ok_or("A configuration file is somehow the root directory")?
+                            .join(file),
+                    ),
+                    None => None,
+                };
+                panic_mapping = config.panic_chord.clone().map(|chord| {
+                    ChordMap::init(
+                        vec![(chord, InnerAction::Panic.into())],
+                        AllAxisThresholds::init(config.axis_thresholds.clone()),
+                    )
+                });
             }
 
+            // Only create the absolute pointer device when it's needed
+            uses_abs_pointer |= !config.abs_mouse_mapping.is_empty();
+
+            // Name the layer for notifications
+            let name = config.display_name.clone().unwrap_or_else(|| {
+                config_paths[i]
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+            layer_names.push(name);
+            led_patterns.push(config.led_pattern.clone());
+            layer_rumbles.push(LayerRumble {
+                activated: config.layer_rumble,
+                chords: config.chord_rumble.clone(),
+            });
+            unmapped_chord_actions.push(config.unmapped_chord.clone());
+
             // Populate mappings
             let mut maps = Self::get_mappings(config);
 
@@ -116,34 +338,72 @@ impl Mapper {
                         }
 
                         // Push new config files to the stack
-                        let p_opt = Some(p);
-                        if !paths_to_indices.contains_key(&p_opt) {
-                            config_paths.push(p_opt.unwrap());
+                        if !paths_to_indices.contains_key(&Some(p.clone())) {
+                            config_paths.push(p);
                         }
                     }
                     Ok(())
                 })?;
 
             // Save mappings
-            paths_to_indices.insert(Some(config_paths[i].clone()), i);
+            paths_to_indices.insert(Some(config_paths[i].clone()), mappings_vec.len());
+            layer_paths.push(config_paths[i].clone());
             mappings_vec.push(maps);
             i += 1;
         }
 
-        let device_name = device_name.unwrap();
+        if uses_abs_pointer {
+            device.enable_abs_pointer()?;
+        }
+        // Also clears the limit of a previously loaded configuration when reloading
+        device
+            .set_key_hold_limit(key_hold_limit)
+            .map_err(|_| "The output thread has stopped")?;
 
         Ok(Self {
             output_device: device,
-            input_device_name: device_name,
+            input_devices,
             current_config_index: 0,
             paths_to_indices,
+            layer_paths,
+            layer_names,
+            led_patterns,
+            led_root: led_root.unwrap_or_else(|| SysfsLeds::DEFAULT_ROOT.into()),
+            leds: None,
+            layer_rumbles,
+            rumble: None,
+            unmapped_chord_actions,
+            unmapped_chord_count: 0,
+            stats_path,
+            stats: None,
+            observers: Vec::new(),
+            notifier_configs,
+            notifiers: None,
             mappings_vec,
+            grab_devices,
+            suspend_passthrough,
+            suspended: false,
+            panic_mapping,
+            count: None,
+            count_timeout,
+            undo_history: VecDeque::new(),
+            undo_limit,
+            chord_output: None,
+            held_keys: HashMap::new(),
+            now: Instant::now(),
         })
     }
 
-    /// Gets the input device name
+    /// Gets the selectors of all input devices used by the configurations, starting with the main
+    /// one
+    pub fn get_input_selectors(&self) -> &[DeviceSelector] {
+        &self.input_devices
+    }
+
+    /// Gets the main input device name, or an empty string if it's selected without a name
+    #[deprecated(note = "devices may be selected by more than their name, use get_input_selectors")]
     pub fn get_input_name(&self) -> &str {
-        &self.input_device_name
+        self.input_devices[0].name.as_deref().unwrap_or("")
     }
 
     fn clear_all(&mut self) {
@@ -159,12 +419,374 @@ impl Mapper {
                     .collect(),
             ),
         );
-        self.handle_action(handsoff.into());
+        self.execute_output(OutputAction::StateChange(handsoff));
+
+        // A stick that moved the pointer from any configuration no longer does, so put the pointer
+        // back where it rests
+        let anchors: Vec<_> = self
+            .mappings_vec
+            .iter_mut()
+            .flat_map(|maps| maps.abs_mouse.release())
+            .collect();
+        for (x, y) in anchors {
+            self.output_device.move_abs_pointer(x, y).ok();
+        }
+
+        // Only the typed text is left to undo once everything is released
+        let is_pulse = |action: &OutputAction| matches!(action, OutputAction::Pulse(_));
+        self.undo_history
+            .iter_mut()
+            .for_each(|(_, output)| output.retain(is_pulse));
+        self.undo_history.retain(|(_, output)| !output.is_empty());
+        if let Some(output) = &mut self.chord_output {
+            output.retain(is_pulse);
+        }
+    }
+
+    /// Returns whether the input devices should currently be exclusively grabbed.
+    ///
+    /// This starts out as configured in the source configuration file, and is changed by
+    /// [InnerAction::ToggleGrab].
+    pub fn wants_grab(&self) -> bool {
+        self.grab_devices
+    }
+
+    /// Returns the path of the configuration file that is currently active.
+    pub fn current_config_path(&self) -> &Path {
+        &self.layer_paths[self.current_config_index]
+    }
+
+    /// Returns the display name of the configuration that is currently active, or its file name if
+    /// it has none.
+    pub fn current_layer_name(&self) -> &str {
+        &self.layer_names[self.current_config_index]
+    }
+
+    /// Starts announcing events with the [Configuration::notifiers].
+    ///
+    /// No notifier runs until this is called, such as during a dry run.
+    pub fn enable_notifiers(&mut self) {
+        self.notifiers = Some(self.notifier_configs.iter().map(|n| n.build()).collect());
+    }
+
+    /// Reports an error to the observers and notifiers, such as an input device disconnecting.
+    pub fn report_error<S: Into<String>>(&mut self, message: S) {
+        self.notify(MapperEvent::Error {
+            message: message.into(),
+        });
+    }
+
+    /// Starts setting the LEDs of the given controllers to the [Configuration::led_pattern] of
+    /// the active configuration, then shows the current pattern.
+    ///
+    /// The controllers are given by their sysfs devices, whose LEDs are looked up under the
+    /// [Configuration::led_root]. See [SysfsLeds::for_devices]. The LEDs are left alone until this
+    /// is called, such as during a dry run.
+    pub fn enable_leds(&mut self, devices: &[PathBuf]) {
+        let leds = SysfsLeds::new(&self.led_root).for_devices(devices);
+        self.set_led_backend(Box::new(leds));
+    }
+
+    /// Replaces the LEDs that show the [Configuration::led_pattern] of the active configuration,
+    /// then shows the current pattern.
+    pub fn set_led_backend(&mut self, leds: Box<dyn LedBackend>) {
+        self.leds = Some(leds);
+        self.refresh_leds();
+    }
+
+    /// Shows the [Configuration::led_pattern] of the active configuration, if it has one.
+    ///
+    /// This happens automatically when switching configurations, but controllers may reset their
+    /// LEDs when they reconnect.
+    pub fn refresh_leds(&mut self) {
+        let pattern = &self.led_patterns[self.current_config_index];
+        if let (Some(leds), Some(pattern)) = (&mut self.leds, pattern) {
+            if let Err(e) = leds.set_pattern(pattern) {
+                warn!("Could not set the controller LEDs: {}", e);
+            }
+        }
+    }
+
+    /// Returns whether any configuration rumbles the input devices, so a backend is needed. See
+    /// [Mapper::set_rumble_backend].
+    pub fn uses_rumble(&self) -> bool {
+        let unmapped = self
+            .unmapped_chord_actions
+            .iter()
+            .any(|action| matches!(action, UnmappedChord::Rumble(..)));
+        unmapped || self.layer_rumbles.iter().any(|rumble| !rumble.is_empty())
+    }
+
+    /// Plays the rumbles of the configurations on the source, if any configuration rumbles.
+    pub fn attach_rumble(&mut self, source: &mut dyn InputSource) {
+        if self.uses_rumble() {
+            self.set_rumble_backend(source.rumble_backend());
+        }
+    }
+
+    /// Returns whether a configuration rumbles without a backend to play it, such as after a
+    /// [Mapper::reload] that added rumble. See [Mapper::attach_rumble].
+    pub fn needs_rumble_backend(&self) -> bool {
+        self.uses_rumble() && self.rumble.is_none()
+    }
+
+    /// Sets what plays the rumbles of the configurations, such as the input devices.
+    ///
+    /// Without a backend, no rumble is played.
+    pub fn set_rumble_backend(&mut self, rumble: Box<dyn RumbleBackend>) {
+        self.rumble = Some(rumble);
+    }
+
+    fn rumble(&mut self, pattern: Option<RumblePattern>) {
+        if let (Some(rumble), Some(pattern)) = (&mut self.rumble, pattern) {
+            if let Err(e) = rumble.rumble(&pattern) {
+                warn!("Could not rumble the input devices: {}", e);
+            }
+        }
+    }
+
+    /// Reports that the current configuration became active, and plays its rumble.
+    fn notify_layer_changed(&mut self) {
+        self.rumble(self.layer_rumbles[self.current_config_index].activated);
+        self.notify(MapperEvent::LayerChanged {
+            layer: self.current_config_path().to_path_buf(),
+            name: self.current_layer_name().to_string(),
+        });
+    }
+
+    /// Returns the inputs of the chord currently being entered in the active configuration.
+    pub fn chord_state(&self) -> Vec<ChordInput> {
+        self.get_chord_mapping().get_state()
+    }
+
+    /// Returns which output keys are held and which relative axes are moving.
+    pub fn output_state(&self) -> Result<OutputState, std::io::Error> {
+        self.output_device.state()
+    }
+
+    /// Switches to one of the loaded configuration files, like [InnerAction::SwitchConfig].
+    ///
+    /// Returns an error if the file isn't the source configuration file or linked from it.
+    pub fn set_config<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref().canonicalize()?;
+        if !self.paths_to_indices.contains_key(&Some(path.clone())) {
+            return Err(format!("{} is not a loaded configuration file", path.display()).into());
+        }
+        self.switch_config(path);
+        Ok(())
+    }
+
+    /// Suspends or resumes mapping, like [InnerAction::Suspend]. Does nothing if mapping is
+    /// already in the requested state.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        if suspended != self.suspended {
+            self.toggle_suspend();
+        }
+    }
+
+    /// Loads every configuration file again, starting from the source configuration file.
+    ///
+    /// All keys and mouse motion are released and the source configuration becomes active.
+    /// Observers, the LED backend, enabled notifiers, whether mapping is suspended and the
+    /// statistics counted so far are kept, but the input devices stay as they were. The rumble
+    /// backend is kept if the reloaded configurations still rumble. If they only start rumbling
+    /// now, [Mapper::needs_rumble_backend] tells to attach one. On error, the current
+    /// configuration stays loaded.
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut reloaded = Self::init_from_file(self.output_device.clone(), &self.layer_paths[0])?;
+        self.clear_all();
+        reloaded.observers = std::mem::take(&mut self.observers);
+        reloaded.leds = self.leds.take();
+        reloaded.rumble = self.rumble.take().filter(|_| reloaded.uses_rumble());
+        reloaded.suspended = self.suspended;
+        if self.notifiers.is_some() {
+            reloaded.enable_notifiers();
+        }
+        reloaded.unmapped_chord_count = self.unmapped_chord_count;
+        if let Some(stats) = self.stats.take() {
+            reloaded.enable_stats(stats);
+        }
+        *self = reloaded;
+        info!("Reloaded {}", self.current_config_path().display());
+        self.refresh_leds();
+        self.notify_layer_changed();
+        Ok(())
+    }
+
+    /// Handles an action as if it was resolved from input, reporting it to the observers with
+    /// [ActionOrigin::Injected].
+    pub fn inject_action(&mut self, action: Action) {
+        self.handle_resolved(action, ActionOrigin::Injected);
+    }
+
+    /// Adds a callback that is given every [MapperEvent], such as every resolved action.
+    pub fn add_observer(&mut self, observer: MapperObserver) {
+        self.observers.push(observer);
+    }
+
+    fn notify(&mut self, event: MapperEvent) {
+        self.observers
+            .iter_mut()
+            .for_each(|observer| observer(&event));
+        self.notifiers.iter_mut().flatten().for_each(|notifier| {
+            if let Err(e) = notifier.notify(&event) {
+                warn!("Could not announce {:?}: {}", event, e);
+            }
+        });
+    }
+
+    /// Reports a newly resolved action to the observers, then handles it.
+    fn handle_resolved(&mut self, action: Action, origin: ActionOrigin) {
+        debug!("{} => {:?}", origin, action);
+        if !self.observers.is_empty() || self.notifiers.is_some() {
+            self.notify(MapperEvent::ActionResolved {
+                action: action.clone(),
+                origin,
+                layer: self.current_config_path().to_path_buf(),
+            });
+        }
+        self.handle_action(action);
+    }
+
+    /// Returns the file set by [Configuration::stats_file], if any.
+    pub fn stats_path(&self) -> Option<&Path> {
+        self.stats_path.as_deref()
+    }
+
+    /// Starts counting how often each mapping fires, adding to the given statistics.
+    ///
+    /// Every mapped chord is listed, even the ones that never fire.
+    pub fn enable_stats(&mut self, mut stats: UsageStats) {
+        for (layer, maps) in self.layer_paths.iter().zip(&self.mappings_vec) {
+            for (chord, action) in maps.chords.mapped_chords() {
+                stats.register_chord(layer, &chord, action);
+            }
+        }
+        self.stats = Some(stats);
+    }
+
+    /// Returns the statistics counted so far, if enabled by [Mapper::enable_stats].
+    pub fn stats(&self) -> Option<&UsageStats> {
+        self.stats.as_ref()
+    }
+
+    /// Saves the statistics counted so far to the [Configuration::stats_file]. Does nothing if
+    /// either isn't set.
+    pub fn save_stats(&self) -> Result<(), Box<dyn Error>> {
+        if let (Some(stats), Some(path)) = (&self.stats, &self.stats_path) {
+            stats.save_to_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Counts a resolved action from one of the mappings, if statistics are enabled.
+    fn record_usage(&mut self, origin: &ActionOrigin, action: &Action, latency: Duration) {
+        if let Some(stats) = &mut self.stats {
+            let layer = &self.layer_paths[self.current_config_index];
+            stats.record(layer, origin, action, latency);
+        }
+    }
+
+    /// Returns how many chords without a mapping were released since the mapper was created.
+    pub fn unmapped_chord_count(&self) -> u64 {
+        self.unmapped_chord_count
+    }
+
+    /// Counts and reports a released chord without a mapping, then reacts as configured.
+    fn handle_unmapped(&mut self, chord: Vec<ChordInput>) {
+        self.unmapped_chord_count += 1;
+        if let Some((count, _)) = self.count.take() {
+            debug!("Count {} cleared by an unmapped chord", count);
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.record_unmapped(&self.layer_paths[self.current_config_index]);
+        }
+        self.notify(MapperEvent::ChordUnmapped {
+            chord: chord.clone(),
+            layer: self.current_config_path().to_path_buf(),
+        });
+
+        let origin = ActionOrigin::UnmappedChord(chord);
+        match self.unmapped_chord_actions[self.current_config_index].clone() {
+            UnmappedChord::Ignore => debug!("{}", origin),
+            UnmappedChord::Warn => warn!("{} in {}", origin, self.current_layer_name()),
+            UnmappedChord::Rumble(pattern) => {
+                debug!("{}", origin);
+                self.rumble(Some(pattern));
+            }
+            UnmappedChord::Action(action) => self.handle_resolved(action, origin),
+        }
+    }
+
+    /// Returns whether mapping is currently suspended by [InnerAction::Suspend].
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns whether raw input events should be forwarded to virtual copies of the input
+    /// devices while suspended.
+    pub fn wants_passthrough(&self) -> bool {
+        self.suspend_passthrough
+    }
+
+    /// Resets the state of every mapping and releases all keys and mouse motion.
+    ///
+    /// This is used when the input devices can no longer be trusted to report the release of
+    /// their inputs, such as after a disconnect. The current configuration stays active.
+    pub fn reset(&mut self) {
+        self.mappings_vec.iter_mut().for_each(|maps| {
+            maps.chords.clear_state();
+            maps.modifiers.clear_state();
+            maps.mouse.clear_state();
+            maps.abs_mouse.clear_state();
+            maps.resume.clear_state();
+        });
+        self.count = None;
+        self.held_keys.clear();
+        self.clear_all();
+    }
+
+    fn panic(&mut self) {
+        if let Some(panic_mapping) = &mut self.panic_mapping {
+            panic_mapping.clear_state();
+        }
+        warn!("Panic chord pressed, resetting to the source configuration");
+        if self.suspended {
+            self.suspended = false;
+            self.notify(MapperEvent::SuspendChanged { suspended: false });
+        }
+        self.current_config_index = 0;
+        self.reset();
+        self.refresh_leds();
+        self.notify_layer_changed();
     }
 
     fn switch_config(&mut self, path: std::path::PathBuf) {
         self.current_config_index = *self.paths_to_indices.get(&Some(path)).unwrap();
+        info!("Switched to {}", self.current_config_path().display());
         self.clear_all();
+        self.refresh_leds();
+        self.notify_layer_changed();
+    }
+
+    fn toggle_suspend(&mut self) {
+        self.suspended = !self.suspended;
+
+        // Forget partially entered input, so inputs held through the switch can't trigger actions
+        let maps = &mut self.mappings_vec[self.current_config_index];
+        maps.chords.clear_state();
+        maps.modifiers.clear_state();
+        maps.mouse.clear_state();
+        maps.abs_mouse.clear_state();
+        maps.resume.clear_state();
+
+        if self.suspended {
+            self.count = None;
+            self.clear_all();
+        }
+        self.notify(MapperEvent::SuspendChanged {
+            suspended: self.suspended,
+        });
     }
 
     fn get_chord_mapping(&self) -> &ChordMap {
@@ -183,6 +805,14 @@ impl Mapper {
         &mut self.mappings_vec[self.current_config_index].mouse
     }
 
+    fn get_abs_mouse_mapping_mut(&mut self) -> &mut AbsMouseMap {
+        &mut self.mappings_vec[self.current_config_index].abs_mouse
+    }
+
+    fn get_resume_mapping_mut(&mut self) -> &mut ChordMap {
+        &mut self.mappings_vec[self.current_config_index].resume
+    }
+
     fn repeat_last_chord(&mut self, act_type: OutputActionType) {
         let act_opt = self.get_chord_mapping().get_prev_action();
         if let Some(Action::OutputAction(act)) = act_opt {
@@ -211,13 +841,66 @@ impl Mapper {
             InnerAction::SwitchConfig(path) => {
                 self.switch_config(path);
             }
+            InnerAction::ToggleGrab => {
+                self.grab_devices = !self.grab_devices;
+                debug!("Input devices should now be grabbed: {}", self.grab_devices);
+            }
+            InnerAction::Suspend => {
+                self.toggle_suspend();
+            }
+            InnerAction::Panic => {
+                self.panic();
+            }
+            InnerAction::CountDigit(digit) => {
+                self.push_count_digit(digit);
+            }
+            InnerAction::UndoLastChord => {
+                self.undo_last_chord();
+            }
+        }
+    }
+
+    fn undo_last_chord(&mut self) {
+        let (chord, output) = match self.undo_history.pop_back() {
+            Some(entry) => entry,
+            None => {
+                debug!("No chord output left to undo");
+                return;
+            }
+        };
+        debug!("Undoing {}", ActionOrigin::Chord(chord));
+        output
+            .iter()
+            .rev()
+            .filter_map(OutputAction::undo)
+            .for_each(|undo| self.execute_output(undo));
+    }
+
+    /// Remembers the output of a chord for [InnerAction::UndoLastChord], forgetting the oldest
+    /// output past the limit.
+    fn push_undo(&mut self, chord: Vec<ChordInput>, output: Vec<OutputAction>) {
+        if output.is_empty() || self.undo_limit == 0 {
+            return;
+        }
+        if self.undo_history.len() >= self.undo_limit {
+            self.undo_history.pop_front();
+        }
+        self.undo_history.push_back((chord, output));
+    }
+
+    fn execute_output(&mut self, action: OutputAction) {
+        if self.output_device.execute_event(action).is_err() {
+            self.report_error("The output device has stopped");
         }
     }
 
     fn handle_action(&mut self, action: Action) {
         match action {
             Action::OutputAction(oe) => {
-                self.output_device.execute_event(oe).ok();
+                if let Some(output) = &mut self.chord_output {
+                    output.push(oe.clone());
+                }
+                self.execute_output(oe);
             }
             Action::InnerAction(ie) => {
                 self.handle_inner_action(ie);
@@ -225,28 +908,1030 @@ impl Mapper {
         }
     }
 
+    /// Rumbles, counts and handles an action emitted by the chord mapping, as many times as the
+    /// pending count if the action uses it.
+    fn handle_chord_action(&mut self, action: Action) {
+        let chord = self.get_chord_mapping().get_emitted_chord();
+        self.rumble(self.layer_rumbles[self.current_config_index].for_chord(&chord));
+        let origin = ActionOrigin::Chord(chord.clone());
+        let latency = self.get_chord_mapping().get_emitted_latency();
+        self.record_usage(&origin, &action, latency);
+
+        let uses_count = matches!(
+            action,
+            Action::OutputAction(_)
+                | Action::InnerAction(InnerAction::RepeatLastChord(_))
+                | Action::InnerAction(InnerAction::UndoLastChord)
+        );
+        let times = match self.pending_count() {
+            Some(count) if uses_count => {
+                self.count = None;
+                debug!("Repeating {:?} {} times", action, count);
+                count
+            }
+            _ => 1,
+        };
+        self.chord_output = Some(Vec::new());
+        self.handle_resolved(action.clone(), origin);
+        for _ in 1..times {
+            self.handle_action(action.clone());
+        }
+        if let Some(output) = self.chord_output.take() {
+            self.push_undo(chord, output);
+        }
+    }
+
+    /// Returns the count entered with [InnerAction::CountDigit] for the next chord, if any.
+    pub fn pending_count(&self) -> Option<u32> {
+        self.count
+            .filter(|(_, expires)| self.now < *expires)
+            .map(|(count, _)| count)
+    }
+
+    /// Appends a digit to the count for the next chord, restarting its timeout.
+    fn push_count_digit(&mut self, digit: u8) {
+        if digit > 9 {
+            warn!("{} isn't a digit, so the count stays as it is", digit);
+            return;
+        }
+        let count = self.pending_count().unwrap_or(0) * 10 + digit as u32;
+        let count = count.min(Self::MAX_COUNT);
+        debug!("Count is now {}", count);
+        self.count = Some((count, self.now + self.count_timeout));
+    }
+
+    /// Returns when [Mapper::tick] should be called next, if a held chord repeats its action or a
+    /// count times out. See [Configuration::chord_repeat].
+    pub fn next_tick(&self) -> Option<Instant> {
+        if self.suspended {
+            return None;
+        }
+        let count_expires = self.count.map(|(_, expires)| expires);
+        match (self.get_chord_mapping().next_tick(), count_expires) {
+            (Some(repeat), Some(expires)) => Some(repeat.min(expires)),
+            (repeat, expires) => repeat.or(expires),
+        }
+    }
+
+    /// Emits the actions of held chords due to repeat by the given time, and clears a count that
+    /// timed out. See [Configuration::chord_repeat].
+    ///
+    /// Every tick due since the last call is caught up on in order, as if each had been called on
+    /// time.
+    pub fn tick(&mut self, now: Instant) {
+        while let Some(due) = self.next_tick().filter(|due| *due <= now) {
+            self.tick_once(due);
+            if self.next_tick() == Some(due) {
+                break;
+            }
+        }
+    }
+
+    fn tick_once(&mut self, now: Instant) {
+        self.now = now;
+        if let Some((count, expires)) = self.count {
+            if now >= expires {
+                debug!("Count {} timed out", count);
+                self.count = None;
+            }
+        }
+        if let Some(action) = self.get_chord_mapping_mut().tick(now) {
+            self.handle_chord_action(action);
+        }
+    }
+
+    /// Handles an [InputEvent] from one of several input devices like [Mapper::handle_event], at
+    /// the time the source gave it if any.
+    ///
+    /// A key held on more than one device at once, such as the same button on two identical
+    /// controllers, is only pressed when the first device presses it and released when the last
+    /// device releases it.
+    pub fn handle_sourced_event(&mut self, ev: &SourcedInputEvent) {
+        if let InputEvent::KeyEvent(key_ev) = &ev.event {
+            let holders = self.held_keys.entry(key_ev.key()).or_default();
+            match key_ev.state() {
+                PressState::Down => holders.insert(ev.source),
+                PressState::Up => holders.remove(&ev.source),
+            };
+            let shared = holders.iter().any(|source| *source != ev.source);
+            if shared {
+                debug!("{:?} is also held on another input device", key_ev.key());
+                return;
+            }
+        }
+        self.handle_event_at(&ev.event, ev.time.unwrap_or_else(Instant::now));
+    }
+
     /// Handles the given [InputEvent], changing the internal state and emitting relevant output
     /// actions.
     pub fn handle_event(&mut self, ev: &InputEvent) {
-        let chord_act_opt = self.get_chord_mapping_mut().handle_event(ev);
+        self.handle_event_at(ev, Instant::now());
+    }
+
+    /// Handles an [InputEvent] that happened at the given time, like [Mapper::handle_event].
+    ///
+    /// Chord repeats, counts and usage statistics measure time by the given instants rather than
+    /// the clock, so that a recorded session replays the same way however fast it's fed in.
+    pub fn handle_event_at(&mut self, ev: &InputEvent, now: Instant) {
+        self.now = now;
+
+        // The panic chord takes priority over every other mapping
+        if let Some(panic_mapping) = &mut self.panic_mapping {
+            if let Some(action) = panic_mapping.handle_event(ev, now) {
+                let origin = ActionOrigin::Chord(panic_mapping.get_emitted_chord());
+                self.handle_resolved(action, origin);
+                return;
+            }
+        }
+
+        // While suspended, only listen for the resume chord
+        if self.suspended {
+            let resume_act_opt = self.get_resume_mapping_mut().handle_event(ev, now);
+            if let Some(action) = resume_act_opt {
+                let origin = ActionOrigin::Chord(self.get_resume_mapping_mut().get_emitted_chord());
+                self.handle_resolved(action, origin);
+            }
+            return;
+        }
+
+        let chord_act_opt = self.get_chord_mapping_mut().handle_event(ev, now);
         if let Some(action) = chord_act_opt {
-            self.handle_action(action);
+            self.handle_chord_action(action);
+        }
+        if let Some(chord) = self.get_chord_mapping_mut().take_unmapped() {
+            self.handle_unmapped(chord);
         }
 
         let modifier_act_dbl_opt = self.get_modifier_mapping_mut().handle_event(ev);
         if let Some((act, next_act_opt)) = modifier_act_dbl_opt {
-            self.handle_action(act);
+            let origin = ActionOrigin::Modifier(ev.clone());
+            self.record_usage(&origin, &act, Duration::ZERO);
+            self.handle_resolved(act, origin.clone());
             if let Some(next_act) = next_act_opt {
-                self.handle_action(next_act);
+                self.handle_resolved(next_act, origin);
             }
         }
 
         let mouse_act_dbl_opt = self.get_mouse_mapping_mut().handle_event(ev);
         if let Some((act, next_act_opt)) = mouse_act_dbl_opt {
-            self.handle_action(act);
+            let origin = ActionOrigin::Mouse(ev.clone());
+            self.record_usage(&origin, &act, Duration::ZERO);
+            self.handle_resolved(act, origin.clone());
             if let Some(next_act) = next_act_opt {
-                self.handle_action(next_act);
+                self.handle_resolved(next_act, origin);
+            }
+        }
+
+        let abs_mouse_pos_opt = self.get_abs_mouse_mapping_mut().handle_event(ev);
+        if let Some((x, y)) = abs_mouse_pos_opt {
+            self.output_device.move_abs_pointer(x, y).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::*;
+    use crate::input::scripted::*;
+    use crate::input::source::InputSource;
+    use crate::output::sink::*;
+    use crate::test_util::TempDir;
+
+    const PRO_KEYBOARD: &str = "configs/nintendo_pro_controller/pro_keyboard.json";
+
+    fn init_recorded(path: &str) -> (Mapper, OutputDevice, RecordingSink) {
+        let recorder = RecordingSink::new();
+        let device = OutputDevice::with_sink(Box::new(recorder.clone()));
+        let mapper = Mapper::init_from_file(device.clone(), path).unwrap();
+        (mapper, device, recorder)
+    }
+
+    /// Edited copies of the shipped pro controller configurations, in a directory of their own
+    /// that's removed once dropped.
+    struct EditedConfigs {
+        dir: TempDir,
+        keyboard: PathBuf,
+        blank: PathBuf,
+    }
+
+    impl EditedConfigs {
+        /// Copies the keyboard and blank configurations, letting the edit change each one by its
+        /// file name.
+        fn new<F: Fn(&str, &mut serde_json::Value)>(edit: F) -> Self {
+            let dir = TempDir::new("configs");
+            let configs = Self {
+                keyboard: dir.join("pro_keyboard.json"),
+                blank: dir.join("pro_blank.json"),
+                dir,
+            };
+            configs.rewrite(edit);
+            configs
+        }
+
+        /// Writes the copies again with another edit, such as before reloading.
+        fn rewrite<F: Fn(&str, &mut serde_json::Value)>(&self, edit: F) {
+            for path in [&self.keyboard, &self.blank] {
+                let name = path.file_name().unwrap().to_str().unwrap();
+                let original = Path::new(PRO_KEYBOARD).with_file_name(name);
+                let mut config: serde_json::Value =
+                    serde_json::from_str(&std::fs::read_to_string(original).unwrap()).unwrap();
+                edit(name, &mut config);
+                std::fs::write(path, config.to_string()).unwrap();
+            }
+        }
+
+        /// Creates a mapper for the keyboard configuration, recording its output.
+        fn init_recorded(&self) -> (Mapper, OutputDevice, RecordingSink) {
+            init_recorded(self.keyboard.to_str().unwrap())
+        }
+    }
+
+    fn key(key: KeyCode, state: PressState) -> InputEvent {
+        InputEvent::KeyEvent(KeyEvent::new(key, state))
+    }
+
+    fn press(mapper: &mut Mapper, keys: &[KeyCode]) {
+        keys.iter()
+            .for_each(|k| mapper.handle_event(&key(*k, PressState::Down)));
+        keys.iter()
+            .for_each(|k| mapper.handle_event(&key(*k, PressState::Up)));
+    }
+
+    fn pulse(key: KeyCode) -> Vec<OutputEvent> {
+        vec![
+            OutputEvent::Key(key, PressState::Up),
+            OutputEvent::Key(key, PressState::Down),
+            OutputEvent::Key(key, PressState::Up),
+        ]
+    }
+
+    #[test]
+    fn pro_chords_emit_keys() {
+        let (mut mapper, device, recorder) = init_recorded(PRO_KEYBOARD);
+
+        // A + B
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_F));
+        recorder.clear();
+
+        // D-pad down is reported as an axis
+        let hat =
+            |state| InputEvent::AbsAxisEvent(AbsAxisEvent::new(AbsAxisCode::ABS_HAT0Y, state));
+        mapper.handle_event(&hat(1));
+        mapper.handle_event(&hat(0));
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_D));
+    }
+
+    #[test]
+    fn keys_held_on_two_devices_are_pressed_once() {
+        let (mut mapper, device, recorder) = init_recorded(PRO_KEYBOARD);
+        let west = |source, state| SourcedInputEvent {
+            source,
+            event: key(KeyCode::BTN_WEST, state),
+            frame_end: true,
+            time: None,
+        };
+
+        // Y on both controllers only emits once the second one lets go
+        mapper.handle_sourced_event(&west(0, PressState::Down));
+        mapper.handle_sourced_event(&west(1, PressState::Down));
+        mapper.handle_sourced_event(&west(0, PressState::Up));
+        device.flush();
+        assert!(recorder.events().is_empty());
+
+        mapper.handle_sourced_event(&west(1, PressState::Up));
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_H));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_device_names_still_work() {
+        let mut config = serde_json::to_value(Configuration::pro_default()).unwrap();
+        let fields = config.as_object_mut().unwrap();
+        fields.remove("additional_devices");
+        fields.insert(
+            "additional_device_names".to_string(),
+            serde_json::json!(["Foot Pedal"]),
+        );
+        let config: Configuration = serde_json::from_value(config).unwrap();
+        assert_eq!(config.additional_devices, vec!["Foot Pedal".into()]);
+
+        let (mapper, _device, _recorder) = init_recorded(PRO_KEYBOARD);
+        assert_eq!(mapper.get_input_name(), "Nintendo Switch Pro Controller");
+    }
+
+    #[test]
+    fn configs_need_a_device_name_or_selector() {
+        let configs = EditedConfigs::new(|name, config| {
+            let fields = config.as_object_mut().unwrap();
+            fields.remove("device_name");
+            if name == "pro_keyboard.json" {
+                fields.insert(
+                    "device_selector".to_string(),
+                    serde_json::json!({"name": "Nintendo Switch Pro*", "vendor": 1406}),
+                );
             }
+        });
+
+        let config = Configuration::load_from_file(&configs.keyboard).unwrap();
+        assert_eq!(
+            config.main_device().name.as_deref(),
+            Some("Nintendo Switch Pro*")
+        );
+        assert_eq!(config.main_device().vendor, Some(1406));
+
+        let error = Configuration::load_from_file(&configs.blank).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("neither device_name nor device_selector"));
+    }
+
+    #[test]
+    fn pro_modifiers_hold_keys() {
+        let (mut mapper, device, recorder) = init_recorded(PRO_KEYBOARD);
+
+        mapper.handle_event(&key(KeyCode::BTN_TR2, PressState::Down));
+        device.flush();
+        assert_eq!(
+            recorder.events(),
+            vec![OutputEvent::Key(KeyCode::KEY_LEFTSHIFT, PressState::Down)]
+        );
+
+        mapper.handle_event(&key(KeyCode::BTN_TR2, PressState::Up));
+        device.flush();
+        assert_eq!(
+            recorder.events().last(),
+            Some(&OutputEvent::Key(KeyCode::KEY_LEFTSHIFT, PressState::Up))
+        );
+    }
+
+    #[test]
+    fn pro_switches_config() {
+        let region = AbsMouseRegion {
+            anchor: (0.5, 0.5),
+            width: 0.5,
+            height: 0.5,
+            max_deflection: 30000,
+            deadzone: 4000,
+        };
+        let configs = EditedConfigs::new(|name, config| {
+            if name == "pro_keyboard.json" {
+                let input = AbsMouseInput {
+                    x: AbsAxisCode::ABS_Z,
+                    y: AbsAxisCode::ABS_RZ,
+                };
+                config["abs_mouse_mapping"] = serde_json::json!([(input, region)]);
+            }
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+        let switch = [
+            KeyCode::BTN_Z,
+            KeyCode::BTN_EAST,
+            KeyCode::BTN_SOUTH,
+            KeyCode::BTN_NORTH,
+            KeyCode::BTN_WEST,
+        ];
+
+        // A stick holding the pointer away from its anchor lets go of it when switching away
+        let stick = InputEvent::AbsAxisEvent(AbsAxisEvent::new(AbsAxisCode::ABS_Z, 30000));
+        mapper.handle_event(&stick);
+        press(&mut mapper, &switch);
+        device.flush();
+        let (x, y) = region.anchor_position();
+        let (deflected_x, deflected_y) = region.position(30000, 0);
+        let pointer = recorder
+            .events()
+            .into_iter()
+            .filter(|ev| matches!(ev, OutputEvent::AbsPointer(..)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pointer,
+            [
+                OutputEvent::AbsPointer(deflected_x, deflected_y),
+                OutputEvent::AbsPointer(x, y),
+            ]
+        );
+        recorder.clear();
+
+        // The blank configuration ignores everything but switching back
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        device.flush();
+        assert!(!recorder
+            .events()
+            .contains(&OutputEvent::Key(KeyCode::KEY_F, PressState::Down)));
+        recorder.clear();
+
+        press(&mut mapper, &switch);
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        device.flush();
+        assert!(recorder.events().ends_with(&pulse(KeyCode::KEY_F)));
+    }
+
+    #[test]
+    fn observers_see_layer_and_suspend_changes() {
+        let (mut mapper, _device, _recorder) = init_recorded(PRO_KEYBOARD);
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen_clone = seen.clone();
+        mapper.add_observer(Box::new(move |ev| {
+            if !matches!(ev, MapperEvent::ActionResolved { .. }) {
+                seen_clone.borrow_mut().push(ev.clone());
+            }
+        }));
+
+        let blank = Path::new(PRO_KEYBOARD).with_file_name("pro_blank.json");
+        mapper.set_config(&blank).unwrap();
+        mapper.set_suspended(true);
+
+        let seen = seen.borrow();
+        assert_eq!(
+            seen.as_slice(),
+            [
+                MapperEvent::LayerChanged {
+                    layer: blank.canonicalize().unwrap(),
+                    name: "pro_blank".to_string(),
+                },
+                MapperEvent::SuspendChanged { suspended: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn led_pattern_follows_the_active_layer() {
+        let led_root = TempDir::new("leds");
+        for player in 1..=4 {
+            let led = led_root.join(format!("0005:057E:2009.0001:green:player-{}", player));
+            std::fs::create_dir_all(&led).unwrap();
+            std::fs::write(led.join("brightness"), "0").unwrap();
         }
+
+        // Copies of the shipped configurations, each with its own LED pattern
+        let configs = EditedConfigs::new(|name, config| {
+            config["led_pattern"] = if name == "pro_keyboard.json" {
+                serde_json::json!([true])
+            } else {
+                serde_json::json!([false, true, true])
+            };
+            config["led_root"] = serde_json::json!(led_root.path());
+        });
+        let lit = || {
+            (1..=4)
+                .filter(|player| {
+                    let led = led_root.join(format!("0005:057E:2009.0001:green:player-{}", player));
+                    std::fs::read_to_string(led.join("brightness")).unwrap() == "1"
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The fake LEDs all belong to a single device
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        assert!(lit().is_empty());
+        mapper.enable_leds(&[led_root.path().to_path_buf()]);
+        assert_eq!(lit(), [1]);
+        mapper.set_config(&configs.blank).unwrap();
+        assert_eq!(lit(), [2, 3]);
+        mapper.panic();
+        assert_eq!(lit(), [1]);
+    }
+
+    #[test]
+    fn leds_and_notifiers_wait_until_enabled() {
+        let led_root = TempDir::new("leds");
+        let led = led_root.join("0005:057E:2009.0001:green:player-1");
+        std::fs::create_dir_all(&led).unwrap();
+        std::fs::write(led.join("brightness"), "0").unwrap();
+        let output = TempDir::new("notify");
+        let announced = output.join("announced");
+        let configs = EditedConfigs::new(|_name, config| {
+            config["led_pattern"] = serde_json::json!([true]);
+            config["led_root"] = serde_json::json!(led_root.path());
+            config["notifiers"] = serde_json::json!([{"Exec": [
+                "sh", "-c", "echo \"$CHORD2KEY_EVENT\" >> \"$0\"", announced
+            ]}]);
+        });
+
+        // Like a dry run, which never enables them
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        mapper.set_config(&configs.blank).unwrap();
+        mapper.report_error("Not announced");
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(
+            std::fs::read_to_string(led.join("brightness")).unwrap(),
+            "0"
+        );
+        assert!(!announced.exists());
+
+        mapper.enable_leds(&[led_root.path().to_path_buf()]);
+        mapper.enable_notifiers();
+        mapper.set_config(&configs.keyboard).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(led.join("brightness")).unwrap(),
+            "1"
+        );
+        let start = Instant::now();
+        while !announced.exists() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(announced.exists());
+    }
+
+    #[test]
+    fn unmapped_chords_are_counted_and_handled() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["unmapped_chord"] = serde_json::json!({
+                "Action": {"OutputAction": {"Pulse": {"keys": ["KEY_SOUND"], "axes": null}}}
+            });
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+
+        // A + B + X + Y isn't mapped. It's handled once, and releasing X and Y first still leaves
+        // A + B to emit F, just like without an unmapped chord action
+        let keys = [
+            KeyCode::BTN_NORTH,
+            KeyCode::BTN_WEST,
+            KeyCode::BTN_EAST,
+            KeyCode::BTN_SOUTH,
+        ];
+        press(&mut mapper, &keys);
+        device.flush();
+        assert_eq!(
+            recorder.events(),
+            [pulse(KeyCode::KEY_SOUND), pulse(KeyCode::KEY_F)].concat()
+        );
+        assert_eq!(mapper.unmapped_chord_count(), 1);
+    }
+
+    #[test]
+    fn releases_outside_a_chord_are_not_unmapped_chords() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["unmapped_chord"] = serde_json::json!({
+                "Action": {"OutputAction": {"Pulse": {"keys": ["KEY_SOUND"], "axes": null}}}
+            });
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+
+        // Right after starting, a modifier and a stray release aren't chords at all
+        press(&mut mapper, &[KeyCode::BTN_TR2]);
+        mapper.handle_event(&key(KeyCode::BTN_WEST, PressState::Up));
+        device.flush();
+        assert!(!recorder
+            .events()
+            .contains(&OutputEvent::Key(KeyCode::KEY_SOUND, PressState::Down)));
+        assert_eq!(mapper.unmapped_chord_count(), 0);
+
+        // Chords still work afterwards
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        device.flush();
+        assert!(recorder.events().ends_with(&pulse(KeyCode::KEY_F)));
+    }
+
+    #[test]
+    fn held_chords_repeat_until_released() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["chord_repeat"] = serde_json::json!([
+                [[{"Key": "BTN_WEST"}], {"delay_ms": 300, "interval_ms": 50}]
+            ]);
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+        let hold = |mapper: &mut Mapper, state| mapper.handle_event(&key(KeyCode::BTN_WEST, state));
+
+        // Tapping still emits once on release
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_H));
+        recorder.clear();
+
+        hold(&mut mapper, PressState::Down);
+        let start = Instant::now();
+        assert!(mapper.next_tick().is_some());
+        mapper.tick(start + Duration::from_millis(100));
+        device.flush();
+        assert!(recorder.events().is_empty());
+
+        let first = start + Duration::from_millis(300);
+        mapper.tick(first);
+        mapper.tick(first + Duration::from_millis(49));
+        mapper.tick(first + Duration::from_millis(50));
+        device.flush();
+        assert_eq!(
+            recorder.events(),
+            [pulse(KeyCode::KEY_H), pulse(KeyCode::KEY_H)].concat()
+        );
+        recorder.clear();
+
+        // Releasing after a repeat emits nothing more, and stops the repeats
+        hold(&mut mapper, PressState::Up);
+        assert_eq!(mapper.next_tick(), None);
+        mapper.tick(first + Duration::from_secs(1));
+        device.flush();
+        assert!(recorder.events().is_empty());
+
+        // Chords without a repeat never need a tick
+        mapper.handle_event(&key(KeyCode::BTN_SOUTH, PressState::Down));
+        assert_eq!(mapper.next_tick(), None);
+    }
+
+    #[test]
+    fn replayed_chords_repeat_by_recorded_time() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["chord_repeat"] = serde_json::json!([
+                [[{"Key": "BTN_WEST"}], {"delay_ms": 300, "interval_ms": 50}]
+            ]);
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+        let ev = |time_ms, state| TimedInputEvent {
+            time_ms,
+            source: 0,
+            event: key(KeyCode::BTN_WEST, state),
+        };
+        let mut source =
+            ScriptedSource::new(vec![ev(0, PressState::Down), ev(420, PressState::Up)]);
+
+        // Unpaced, the whole session arrives at once, so only the recorded times can tell the
+        // mapper that Y was held long enough to repeat at 300, 350 and 400 ms
+        source
+            .poll_timeout(Duration::ZERO, &mut |ev| {
+                mapper.tick(ev.time.unwrap());
+                mapper.handle_sourced_event(ev);
+            })
+            .unwrap();
+        assert!(source.is_finished());
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_H).repeat(3));
+    }
+
+    #[test]
+    fn count_digits_repeat_the_next_chord() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["count_timeout_ms"] = serde_json::json!(1000);
+            config["chord_mapping"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!([
+                    [{"Key": "BTN_EAST"}, {"Key": "BTN_WEST"}],
+                    {"InnerAction": {"CountDigit": 3}}
+                ]));
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+        let digit = [KeyCode::BTN_EAST, KeyCode::BTN_WEST];
+
+        press(&mut mapper, &digit);
+        assert_eq!(mapper.pending_count(), Some(3));
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_H).repeat(3));
+        assert_eq!(mapper.pending_count(), None);
+        recorder.clear();
+
+        // Digits build up, and an unmapped chord throws the count away before A + B is left
+        press(&mut mapper, &digit);
+        press(&mut mapper, &digit);
+        assert_eq!(mapper.pending_count(), Some(33));
+        press(
+            &mut mapper,
+            &[
+                KeyCode::BTN_NORTH,
+                KeyCode::BTN_WEST,
+                KeyCode::BTN_EAST,
+                KeyCode::BTN_SOUTH,
+            ],
+        );
+        assert_eq!(mapper.pending_count(), None);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_F));
+        recorder.clear();
+
+        // The count is dropped once it times out
+        press(&mut mapper, &digit);
+        let expires = mapper.next_tick().unwrap();
+        mapper.tick(expires);
+        assert_eq!(mapper.pending_count(), None);
+        assert_eq!(mapper.next_tick(), None);
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_H));
+    }
+
+    #[test]
+    fn undo_reverts_the_output_of_recent_chords() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["undo_limit"] = serde_json::json!(2);
+            config["chord_mapping"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!([
+                    [{"Key": "BTN_EAST"}, {"Key": "BTN_WEST"}],
+                    {"InnerAction": "UndoLastChord"}
+                ]));
+        });
+        let (mut mapper, device, recorder) = configs.init_recorded();
+        let undo = [KeyCode::BTN_EAST, KeyCode::BTN_WEST];
+
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        press(&mut mapper, &[KeyCode::BTN_THUMBL, KeyCode::BTN_SOUTH]);
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        device.flush();
+        recorder.clear();
+
+        // Only the last two chords are remembered, and undone in reverse
+        press(&mut mapper, &undo);
+        press(&mut mapper, &undo);
+        press(&mut mapper, &undo);
+        device.flush();
+        assert_eq!(
+            recorder.events(),
+            [
+                pulse(KeyCode::KEY_BACKSPACE),
+                vec![OutputEvent::Key(KeyCode::BTN_LEFT, PressState::Up)],
+            ]
+            .concat()
+        );
+        recorder.clear();
+
+        // Switching layers releases every key, leaving only typed text to undo
+        press(&mut mapper, &[KeyCode::BTN_THUMBL, KeyCode::BTN_SOUTH]);
+        press(&mut mapper, &[KeyCode::BTN_WEST]);
+        mapper.handle_action(InnerAction::SwitchConfig(configs.blank.clone()).into());
+        mapper.handle_action(InnerAction::SwitchConfig(configs.keyboard.clone()).into());
+        device.flush();
+        recorder.clear();
+        press(&mut mapper, &undo);
+        press(&mut mapper, &undo);
+        device.flush();
+        assert_eq!(recorder.events(), pulse(KeyCode::KEY_BACKSPACE));
+    }
+
+    #[test]
+    fn stats_count_chords_and_persist() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["stats_file"] = serde_json::json!("usage.json");
+        });
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        assert_eq!(
+            mapper.stats_path(),
+            Some(configs.dir.join("usage.json").as_path())
+        );
+        mapper.enable_stats(UsageStats::default());
+
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        press(&mut mapper, &[KeyCode::BTN_SOUTH, KeyCode::BTN_EAST]);
+        press(
+            &mut mapper,
+            &[
+                KeyCode::BTN_EAST,
+                KeyCode::BTN_SOUTH,
+                KeyCode::BTN_NORTH,
+                KeyCode::BTN_WEST,
+            ],
+        );
+        mapper.save_stats().unwrap();
+
+        let stats = UsageStats::load_from_file(configs.dir.join("usage.json")).unwrap();
+        let usage = &stats.layers[&configs.keyboard.canonicalize().unwrap()];
+        assert_eq!(usage.chords["BTN_EAST + BTN_SOUTH"].count, 2);
+        assert_eq!(usage.unmapped_chords, 1);
+
+        // Chords that never fired are listed too
+        let ranked = usage.ranked_chords();
+        assert_eq!(
+            ranked.len(),
+            mapper.get_chord_mapping().mapped_chords().count()
+        );
+        assert_eq!(ranked.last().unwrap().1.count, 0);
+    }
+
+    /// Remembers every played rumble.
+    #[derive(Clone, Default)]
+    struct RecordingRumble(std::rc::Rc<std::cell::RefCell<Vec<u16>>>);
+
+    impl RumbleBackend for RecordingRumble {
+        fn rumble(&mut self, pattern: &RumblePattern) -> Result<(), std::io::Error> {
+            self.0.borrow_mut().push(pattern.duration_ms);
+            Ok(())
+        }
+    }
+
+    /// Remembers every LED pattern that was set.
+    #[derive(Clone, Default)]
+    struct RecordingLeds(std::rc::Rc<std::cell::RefCell<Vec<Vec<bool>>>>);
+
+    impl LedBackend for RecordingLeds {
+        fn set_pattern(&mut self, pattern: &[bool]) -> Result<(), std::io::Error> {
+            self.0.borrow_mut().push(pattern.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reload_keeps_the_leds_and_replaces_the_hold_limit() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["key_hold_limit_ms"] = serde_json::json!(50);
+            config["led_pattern"] = serde_json::json!([true]);
+        });
+        let (mut mapper, device, _recorder) = configs.init_recorded();
+        let leds = RecordingLeds::default();
+        mapper.set_led_backend(Box::new(leds.clone()));
+        let change = |state| {
+            let keys = KeyStateChange {
+                keys: vec![KeyCode::KEY_W],
+                state,
+            };
+            OutputAction::StateChange(StateChange::new(Some(keys), None))
+        };
+        let held_after_limit = || {
+            device.execute_event(change(PressState::Down)).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            let held = device.state().unwrap().held_keys;
+            device.execute_event(change(PressState::Up)).unwrap();
+            held.contains(&KeyCode::KEY_W)
+        };
+        assert!(!held_after_limit());
+
+        configs.rewrite(|_name, config| {
+            config["led_pattern"] = serde_json::json!([false, true]);
+        });
+        mapper.reload().unwrap();
+        assert_eq!(*leds.0.borrow(), [vec![true], vec![false, true]]);
+        assert!(held_after_limit());
+    }
+
+    /// A source without any events, whose rumbles are recorded.
+    struct RumblingSource(RecordingRumble);
+
+    impl InputSource for RumblingSource {
+        fn poll_timeout(
+            &mut self,
+            _timeout: Duration,
+            _callback: &mut dyn FnMut(&SourcedInputEvent),
+        ) -> Result<(), std::io::Error> {
+            Ok(())
+        }
+
+        fn rumble_backend(&mut self) -> Box<dyn RumbleBackend> {
+            Box::new(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn rumble_on_layer_changes_unmapped_and_configured_chords() {
+        let rumble = |duration_ms| serde_json::json!({ "duration_ms": duration_ms });
+        let configs = EditedConfigs::new(|name, config| {
+            if name == "pro_keyboard.json" {
+                config["layer_rumble"] = rumble(1);
+                config["unmapped_chord"] = serde_json::json!({ "Rumble": rumble(2) });
+                config["chord_rumble"] = serde_json::json!([[
+                    [{ "Key": "BTN_SOUTH" }, { "Key": "BTN_EAST" }],
+                    rumble(3)
+                ]]);
+            } else {
+                config["layer_rumble"] = rumble(4);
+            }
+        });
+
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        let played = RecordingRumble::default();
+        mapper.attach_rumble(&mut RumblingSource(played.clone()));
+
+        // A + B is configured, while A + B + X + Y isn't mapped at all
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+        press(
+            &mut mapper,
+            &[
+                KeyCode::BTN_EAST,
+                KeyCode::BTN_SOUTH,
+                KeyCode::BTN_NORTH,
+                KeyCode::BTN_WEST,
+            ],
+        );
+        mapper.set_config(&configs.blank).unwrap();
+        mapper.panic();
+        assert_eq!(*played.0.borrow(), [3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn unmapped_chord_rumble_alone_attaches_the_source() {
+        let configs = EditedConfigs::new(|_name, config| {
+            config["unmapped_chord"] = serde_json::json!({ "Rumble": { "duration_ms": 2 } });
+        });
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        assert!(mapper.uses_rumble());
+        let played = RecordingRumble::default();
+        mapper.attach_rumble(&mut RumblingSource(played.clone()));
+
+        press(
+            &mut mapper,
+            &[
+                KeyCode::BTN_EAST,
+                KeyCode::BTN_SOUTH,
+                KeyCode::BTN_NORTH,
+                KeyCode::BTN_WEST,
+            ],
+        );
+        assert_eq!(*played.0.borrow(), [2]);
+    }
+
+    #[test]
+    fn reload_stays_suspended_and_asks_for_new_rumble() {
+        let configs = EditedConfigs::new(|_name, _config| {});
+        let (mut mapper, _device, _recorder) = configs.init_recorded();
+        let played = RecordingRumble::default();
+        mapper.attach_rumble(&mut RumblingSource(played.clone()));
+        assert!(!mapper.needs_rumble_backend());
+        mapper.set_suspended(true);
+
+        configs.rewrite(|_name, config| {
+            config["layer_rumble"] = serde_json::json!({ "duration_ms": 1 });
+        });
+        mapper.reload().unwrap();
+        assert!(mapper.is_suspended());
+        assert!(mapper.needs_rumble_backend());
+
+        mapper.attach_rumble(&mut RumblingSource(played.clone()));
+        assert!(!mapper.needs_rumble_backend());
+        mapper.set_config(&configs.blank).unwrap();
+        assert_eq!(*played.0.borrow(), [1]);
+    }
+
+    #[test]
+    fn scripted_session() {
+        let (mut mapper, device, recorder) = init_recorded(PRO_KEYBOARD);
+        let ev = |time_ms, k, state| TimedInputEvent {
+            time_ms,
+            source: 0,
+            event: key(k, state),
+        };
+        let mut source = ScriptedSource::new(vec![
+            // Y + X types G
+            ev(0, KeyCode::BTN_WEST, PressState::Down),
+            ev(10, KeyCode::BTN_NORTH, PressState::Down),
+            ev(60, KeyCode::BTN_NORTH, PressState::Up),
+            ev(70, KeyCode::BTN_WEST, PressState::Up),
+            // A + B types F
+            ev(200, KeyCode::BTN_EAST, PressState::Down),
+            ev(210, KeyCode::BTN_SOUTH, PressState::Down),
+            ev(260, KeyCode::BTN_SOUTH, PressState::Up),
+            ev(270, KeyCode::BTN_EAST, PressState::Up),
+        ]);
+
+        while !source.is_finished() {
+            source
+                .poll_timeout(Duration::from_millis(10), &mut |ev| {
+                    mapper.handle_event(&ev.event)
+                })
+                .unwrap();
+        }
+        device.flush();
+
+        let mut expected = pulse(KeyCode::KEY_G);
+        expected.extend(pulse(KeyCode::KEY_F));
+        assert_eq!(recorder.events(), expected);
+    }
+
+    #[test]
+    fn observers_see_resolved_actions() {
+        let (mut mapper, _device, _recorder) = init_recorded(PRO_KEYBOARD);
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen_clone = seen.clone();
+        mapper.add_observer(Box::new(move |ev| seen_clone.borrow_mut().push(ev.clone())));
+
+        press(&mut mapper, &[KeyCode::BTN_EAST, KeyCode::BTN_SOUTH]);
+
+        let layer = Path::new(PRO_KEYBOARD).canonicalize().unwrap();
+        let mut chord = vec![KeyCode::BTN_EAST.into(), KeyCode::BTN_SOUTH.into()];
+        let seen = seen.borrow();
+        match seen.as_slice() {
+            [MapperEvent::ActionResolved {
+                action,
+                origin: ActionOrigin::Chord(inputs),
+                layer: seen_layer,
+            }] => {
+                let mut inputs = inputs.clone();
+                inputs.sort_by_key(|i| format!("{:?}", i));
+                chord.sort_by_key(|i| format!("{:?}", i));
+                assert_eq!(inputs, chord);
+                assert_eq!(seen_layer, &layer);
+                assert_eq!(action, &Pulse::new(Some(vec![KeyCode::KEY_F]), None).into());
+            }
+            other => panic!("Unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shutdown_releases_held_keys() {
+        let (mut mapper, device, recorder) = init_recorded(PRO_KEYBOARD);
+
+        mapper.handle_event(&key(KeyCode::BTN_TR2, PressState::Down));
+        drop(mapper);
+        device.shutdown();
+        assert_eq!(
+            recorder.events(),
+            vec![
+                OutputEvent::Key(KeyCode::KEY_LEFTSHIFT, PressState::Down),
+                OutputEvent::Key(KeyCode::KEY_LEFTSHIFT, PressState::Up),
+            ]
+        );
     }
 }
@@ -1,3 +1,4 @@
+pub mod abs_mouse_map;
 pub mod chord_map;
 pub mod modifier_map;
 pub mod mouse_map;
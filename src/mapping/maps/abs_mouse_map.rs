@@ -0,0 +1,238 @@
+use crate::constants::*;
+use crate::events::*;
+use crate::input::events::*;
+use crate::output::device::OutputDevice;
+use serde::{Deserialize, Serialize};
+
+/// The input type used for absolute mouse mapping: a pair of stick axes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AbsMouseInput {
+    /// The axis controlling the horizontal pointer position
+    pub x: AbsAxisCode,
+
+    /// The axis controlling the vertical pointer position
+    pub y: AbsAxisCode,
+}
+
+/// A list of tuples of mapping [AbsMouseInput] to [AbsMouseRegion]
+pub type AbsMouseMapInput = Vec<(AbsMouseInput, AbsMouseRegion)>;
+
+/// The screen region that the deflection of a stick pair is mapped onto.
+///
+/// All screen coordinates are normalized, so (0.0, 0.0) is the top left corner of the screen and
+/// (1.0, 1.0) is the bottom right corner.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct AbsMouseRegion {
+    /// The position of the pointer when the stick is at rest.
+    ///
+    /// The pointer snaps back to this position whenever the stick is released, and when switching
+    /// to another configuration.
+    pub anchor: (f64, f64),
+
+    /// The width of the rectangle around the anchor that the stick deflection spans.
+    pub width: f64,
+
+    /// The height of the rectangle around the anchor that the stick deflection spans.
+    pub height: f64,
+
+    /// The magnitude of axis deflection that reaches the edge of the rectangle.
+    pub max_deflection: AxisState,
+
+    /// The magnitude of axis deflection at or below which the stick is considered released.
+    pub deadzone: AxisState,
+}
+
+impl AbsMouseRegion {
+    /// Converts a normalized screen coordinate into an absolute pointer position.
+    fn to_pointer(coord: f64) -> AxisState {
+        (coord.clamp(0.0, 1.0) * OutputDevice::abs_pointer_max() as f64).round() as AxisState
+    }
+
+    /// Returns the absolute pointer position of the anchor.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::mapping::mapper::*;
+    /// use chord2key::output::device::OutputDevice;
+    ///
+    /// let region = AbsMouseRegion {
+    ///     anchor: (0.5, 0.0),
+    ///     width: 0.5,
+    ///     height: 0.5,
+    ///     max_deflection: 30000,
+    ///     deadzone: 4000,
+    /// };
+    ///
+    /// let max = OutputDevice::abs_pointer_max();
+    /// assert_eq!(region.anchor_position(), (max / 2 + 1, 0));
+    /// ```
+    pub fn anchor_position(&self) -> (AxisState, AxisState) {
+        (
+            Self::to_pointer(self.anchor.0),
+            Self::to_pointer(self.anchor.1),
+        )
+    }
+
+    /// Returns the absolute pointer position for the given stick deflection.
+    ///
+    /// Deflections past [AbsMouseRegion::max_deflection] stay on the edge of the rectangle, and the
+    /// position never leaves the screen.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::mapping::mapper::*;
+    /// use chord2key::output::device::OutputDevice;
+    ///
+    /// let region = AbsMouseRegion {
+    ///     anchor: (0.5, 0.5),
+    ///     width: 0.5,
+    ///     height: 0.5,
+    ///     max_deflection: 30000,
+    ///     deadzone: 4000,
+    /// };
+    ///
+    /// let max = OutputDevice::abs_pointer_max() as f64;
+    /// let (x, y) = region.position(30000, -60000);
+    /// assert_eq!(x, (0.75 * max).round() as i32);
+    /// assert_eq!(y, (0.25 * max).round() as i32);
+    /// ```
+    pub fn position(&self, x: AxisState, y: AxisState) -> (AxisState, AxisState) {
+        let max_deflection = self.max_deflection.max(1) as f64;
+        let x_ratio = (x as f64 / max_deflection).clamp(-1.0, 1.0);
+        let y_ratio = (y as f64 / max_deflection).clamp(-1.0, 1.0);
+        (
+            Self::to_pointer(self.anchor.0 + x_ratio * self.width / 2.0),
+            Self::to_pointer(self.anchor.1 + y_ratio * self.height / 2.0),
+        )
+    }
+
+    /// Returns whether the stick deflection is inside the deadzone.
+    pub fn is_released(&self, x: AxisState, y: AxisState) -> bool {
+        x.abs() <= self.deadzone && y.abs() <= self.deadzone
+    }
+}
+
+/// The tracked state of a single stick pair.
+struct AbsMouseState {
+    input: AbsMouseInput,
+    region: AbsMouseRegion,
+    x: AxisState,
+    y: AxisState,
+    active: bool,
+}
+
+pub struct AbsMouseMap {
+    states: Vec<AbsMouseState>,
+}
+
+impl AbsMouseMap {
+    fn handle_axis(&mut self, ev: &AbsAxisEvent) -> Option<(AxisState, AxisState)> {
+        let state = self
+            .states
+            .iter_mut()
+            .find(|s| s.input.x == ev.axis() || s.input.y == ev.axis())?;
+
+        if state.input.x == ev.axis() {
+            state.x = ev.state();
+        } else {
+            state.y = ev.state();
+        }
+
+        if state.region.is_released(state.x, state.y) {
+            if state.active {
+                // Stick was released, so snap back to the anchor
+                state.active = false;
+                return Some(state.region.anchor_position());
+            }
+            return None;
+        }
+
+        state.active = true;
+        Some(state.region.position(state.x, state.y))
+    }
+
+    /// Forgets every deflected stick, returning the anchor positions to snap the pointer back to.
+    pub fn release(&mut self) -> Vec<(AxisState, AxisState)> {
+        let anchors = self
+            .states
+            .iter()
+            .filter(|state| state.active)
+            .map(|state| state.region.anchor_position())
+            .collect();
+        self.clear_state();
+        anchors
+    }
+
+    pub fn clear_state(&mut self) {
+        self.states.iter_mut().for_each(|state| {
+            state.x = 0;
+            state.y = 0;
+            state.active = false;
+        });
+    }
+
+    pub fn handle_event(&mut self, ev: &InputEvent) -> Option<(AxisState, AxisState)> {
+        match ev {
+            InputEvent::KeyEvent(_) => None,
+            InputEvent::AbsAxisEvent(aev) => self.handle_axis(aev),
+            InputEvent::RelAxisEvent(_) => None,
+        }
+    }
+
+    pub fn init(abs_mouse_map: AbsMouseMapInput) -> Self {
+        let states = abs_mouse_map
+            .into_iter()
+            .map(|(input, region)| AbsMouseState {
+                input,
+                region,
+                x: 0,
+                y: 0,
+                active: false,
+            })
+            .collect();
+        Self { states }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_back_on_release() {
+        let region = AbsMouseRegion {
+            anchor: (0.5, 0.5),
+            width: 0.5,
+            height: 0.5,
+            max_deflection: 30000,
+            deadzone: 4000,
+        };
+        let input = AbsMouseInput {
+            x: AbsAxisCode::ABS_X,
+            y: AbsAxisCode::ABS_Y,
+        };
+        let mut map = AbsMouseMap::init(vec![(input, region)]);
+        let x_event =
+            |state| InputEvent::AbsAxisEvent(AbsAxisEvent::new(AbsAxisCode::ABS_X, state));
+
+        // Movement inside the deadzone is ignored
+        assert_eq!(map.handle_event(&x_event(2000)), None);
+
+        // Deflection moves the pointer away from the anchor
+        assert_eq!(
+            map.handle_event(&x_event(30000)),
+            Some(region.position(30000, 0))
+        );
+
+        // Releasing the stick snaps back to the anchor, once
+        assert_eq!(
+            map.handle_event(&x_event(100)),
+            Some(region.anchor_position())
+        );
+        assert_eq!(map.handle_event(&x_event(0)), None);
+
+        // Unrelated axes are ignored
+        let other = InputEvent::AbsAxisEvent(AbsAxisEvent::new(AbsAxisCode::ABS_RX, 30000));
+        assert_eq!(map.handle_event(&other), None);
+    }
+}
@@ -4,10 +4,13 @@ use crate::events::*;
 use crate::input::events::InputEvent;
 use crate::mapping::actions::*;
 use crate::mapping::thresholds::*;
+use log::{trace, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::matches;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 /// A list of tuples that map a list of [ChordInput] to an [Action].
 pub type ChordMapInput = Vec<(Vec<ChordInput>, Action)>;
@@ -22,6 +25,27 @@ pub enum ChordInput {
     ThresholdedAxis(ThresholdedAxis),
 }
 
+/// How a chord repeats its action while held, like a held key on a keyboard.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ChordRepeat {
+    /// How long the chord is held before its action is emitted, in milliseconds.
+    pub delay_ms: u64,
+
+    /// How long to wait between every repeat after that, in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl ChordRepeat {
+    fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    fn interval(&self) -> Duration {
+        // Never repeat on every tick
+        Duration::from_millis(self.interval_ms.max(1))
+    }
+}
+
 pub struct ChordMap {
     chord_inputs: Rc<AttributeSet<ChordInput>>,
     axis_thresholds: AllAxisThresholds,
@@ -29,6 +53,28 @@ pub struct ChordMap {
     primed: bool,
     state: Chord,
     prev_chord: Chord,
+    emitted_chord: Chord,
+
+    /// A chord released without a mapping that hasn't been taken yet.
+    unmapped: Option<Vec<ChordInput>>,
+
+    /// Whether the chord being released was already found to be unmapped.
+    unmapped_found: bool,
+
+    /// When the first input of the chord being entered was pressed.
+    started: Instant,
+
+    /// How long the chord that emitted the most recent action was entered for.
+    emitted_latency: Duration,
+
+    /// The chords that repeat their action while held.
+    repeats: HashMap<Chord, ChordRepeat>,
+
+    /// When the most recent input joined the chord being entered.
+    changed: Instant,
+
+    /// When the held chord repeats its action again, once it has been emitted by repeating.
+    next_repeat: Option<Instant>,
 }
 
 impl ChordMap {
@@ -73,11 +119,122 @@ impl ChordMap {
         self.chord_mapping.get(chord).cloned()
     }
 
+    /// Returns every mapped chord along with its action.
+    pub fn mapped_chords(&self) -> impl Iterator<Item = (Vec<ChordInput>, &Action)> {
+        self.chord_mapping
+            .iter()
+            .map(|(chord, action)| (chord.items().copied().collect(), action))
+    }
+
     pub fn get_prev_action(&self) -> Option<Action> {
         self.chord_mapping.get(&self.prev_chord).cloned()
     }
 
-    fn emit_action(&mut self) -> Option<Action> {
+    /// Returns the inputs that are currently part of the chord being entered.
+    pub fn get_state(&self) -> Vec<ChordInput> {
+        self.state.items().copied().collect()
+    }
+
+    /// Returns the inputs of the chord that emitted the most recent action.
+    pub fn get_emitted_chord(&self) -> Vec<ChordInput> {
+        self.emitted_chord.items().copied().collect()
+    }
+
+    /// Returns the time from the first press of the chord that emitted the most recent action
+    /// until it emitted.
+    pub fn get_emitted_latency(&self) -> Duration {
+        self.emitted_latency
+    }
+
+    /// Adds an input to the chord being entered, returning whether it's one of the chord inputs.
+    /// Other inputs can't be part of a chord, so they're left out.
+    fn insert(&mut self, input: &ChordInput, now: Instant) -> bool {
+        if self.state.items().next().is_none() {
+            self.started = now;
+        }
+        let inserted = self.state.try_insert(input).is_ok();
+        if inserted {
+            self.changed = now;
+            self.next_repeat = None;
+        }
+        inserted
+    }
+
+    /// Makes the chords repeat their action while held. Chords that aren't mapped are ignored.
+    pub fn set_repeats(&mut self, repeats: Vec<(Vec<ChordInput>, ChordRepeat)>) {
+        self.repeats.clear();
+        for (chord, repeat) in repeats {
+            let subset = self.chord_inputs.subset_with(chord.iter().copied());
+            let known = chord.iter().all(|input| self.chord_inputs.contains(input));
+            if known && self.chord_mapping.contains_key(&subset) {
+                self.repeats.insert(subset, repeat);
+            } else {
+                warn!(
+                    "Chord {:?} isn't mapped to an action, so it can't repeat",
+                    chord
+                );
+            }
+        }
+    }
+
+    /// Returns when [ChordMap::tick] should be called next, if the held chord repeats its action.
+    pub fn next_tick(&self) -> Option<Instant> {
+        let repeat = self.repeats.get(&self.state)?;
+        if self.primed {
+            Some(self.changed + repeat.delay())
+        } else {
+            self.next_repeat
+        }
+    }
+
+    /// Returns the action of the held chord if it's due to repeat at the given time.
+    ///
+    /// The first repeat takes the place of the action emitted on release, so releasing the chord
+    /// afterwards emits nothing. A chord released before its delay emits on release as usual.
+    pub fn tick(&mut self, now: Instant) -> Option<Action> {
+        let due = self.next_tick()?;
+        if now < due {
+            return None;
+        }
+        let interval = self.repeats[&self.state].interval();
+
+        let action = if self.primed {
+            self.emit_action(now)
+        } else {
+            self.get_action(&self.emitted_chord)
+        };
+        if action.is_some() {
+            trace!(
+                "Chord {:?} repeated",
+                self.state.items().collect::<Vec<_>>()
+            );
+            self.next_repeat = Some(now + interval);
+        }
+        action
+    }
+
+    /// Returns the inputs of the most recent chord that was released without being mapped to an
+    /// action, if there was one since the last call.
+    pub fn take_unmapped(&mut self) -> Option<Vec<ChordInput>> {
+        self.unmapped.take()
+    }
+
+    /// Sets whether the chord map is ready to emit an action, logging any change.
+    fn set_primed(&mut self, primed: bool) {
+        if primed != self.primed {
+            trace!(
+                "Chord {:?} {}",
+                self.state.items().collect::<Vec<_>>(),
+                if primed { "primed" } else { "unprimed" }
+            );
+        }
+        self.primed = primed;
+        if primed {
+            self.unmapped_found = false;
+        }
+    }
+
+    fn emit_action(&mut self, now: Instant) -> Option<Action> {
         let mut action: Option<Action> = None;
 
         // If ready to emit an action
@@ -85,6 +242,10 @@ impl ChordMap {
             // See if the chord results in an action
             action = self.get_action(&self.state);
             if let Some(ref action) = action {
+                // Remember which chord emitted the action
+                self.emitted_chord.copy_from(&self.state).ok();
+                self.emitted_latency = now.saturating_duration_since(self.started);
+
                 // Update previous chord for chord repition
                 if !matches!(
                     action,
@@ -94,34 +255,44 @@ impl ChordMap {
                 }
 
                 // No longer ready to emit actions
-                self.primed = false;
+                self.set_primed(false);
+            } else if !self.unmapped_found && self.state.items().next().is_some() {
+                // Only report the full chord, not each smaller chord left while releasing it, and never
+                // an empty one
+                self.unmapped = Some(self.get_state());
+                self.unmapped_found = true;
             }
         }
 
         action
     }
 
-    fn handle_key(&mut self, ev: &KeyEvent) -> Option<Action> {
+    fn handle_key(&mut self, ev: &KeyEvent, now: Instant) -> Option<Action> {
         let key = &ChordInput::Key(ev.key());
 
         match ev.state() {
             PressState::Down => {
-                if self.state.try_insert(key).is_ok() {
-                    self.primed = true;
+                if self.insert(key, now) {
+                    self.set_primed(true);
                 }
                 None
             }
             PressState::Up => {
-                let action = self.emit_action();
+                // Releasing an input that isn't part of the chord doesn't finish it
+                if !self.state.contains(key) {
+                    return None;
+                }
+                let action = self.emit_action(now);
 
                 self.state.remove(key);
+                self.next_repeat = None;
 
                 action
             }
         }
     }
 
-    fn handle_axis(&mut self, ev: &AbsAxisEvent) -> Option<Action> {
+    fn handle_axis(&mut self, ev: &AbsAxisEvent, now: Instant) -> Option<Action> {
         let (possible1, possible2) = ThresholdedAxis::all_possible(ev);
         let possible1: &ChordInput = &possible1.into();
         let possible2: &ChordInput = &possible2.into();
@@ -137,20 +308,25 @@ impl ChordMap {
                 if self.state.contains(&passing_t.into()) {
                     return None;
                 }
+                trace!("{:?} crossed {:?}", ev, passing_t);
                 if self.state.contains(possible1) || self.state.contains(possible2) {
-                    action = self.emit_action();
+                    action = self.emit_action(now);
                     self.state.remove(&passing_t.opposite().into());
                 }
-                self.primed = true;
-                self.state.try_insert(&passing_t.into()).ok();
+                self.insert(&passing_t.into(), now);
+                self.set_primed(true);
             }
             None => {
+                if self.state.contains(possible1) || self.state.contains(possible2) {
+                    trace!("{:?} receded from every threshold", ev);
+                    self.next_repeat = None;
+                }
                 if self.state.contains(possible1) {
-                    action = self.emit_action();
+                    action = self.emit_action(now);
                     self.state.remove(possible1);
                 }
                 if self.state.contains(possible2) {
-                    action = self.emit_action();
+                    action = self.emit_action(now);
                     self.state.remove(possible2);
                 }
             }
@@ -161,12 +337,15 @@ impl ChordMap {
 
     pub fn clear_state(&mut self) {
         self.state.clear();
+        self.unmapped = None;
+        self.next_repeat = None;
     }
 
-    pub fn handle_event(&mut self, ev: &InputEvent) -> Option<Action> {
+    /// Handles an input that happened at the given time, returning the action it emits, if any.
+    pub fn handle_event(&mut self, ev: &InputEvent, now: Instant) -> Option<Action> {
         match ev {
-            InputEvent::KeyEvent(kev) => self.handle_key(kev),
-            InputEvent::AbsAxisEvent(aev) => self.handle_axis(aev),
+            InputEvent::KeyEvent(kev) => self.handle_key(kev, now),
+            InputEvent::AbsAxisEvent(aev) => self.handle_axis(aev, now),
             InputEvent::RelAxisEvent(_) => None,
         }
     }
@@ -177,6 +356,7 @@ impl ChordMap {
 
         let state = chord_inputs.empty_subset();
         let prev_chord = chord_inputs.empty_subset();
+        let emitted_chord = chord_inputs.empty_subset();
 
         let mut new_self = Self {
             chord_inputs,
@@ -185,6 +365,14 @@ impl ChordMap {
             primed: true,
             state,
             prev_chord,
+            emitted_chord,
+            unmapped: None,
+            unmapped_found: false,
+            started: Instant::now(),
+            emitted_latency: Duration::ZERO,
+            repeats: HashMap::new(),
+            changed: Instant::now(),
+            next_repeat: None,
         };
         new_self.fill_chords(chord_map);
 
@@ -192,6 +380,23 @@ impl ChordMap {
     }
 }
 
+/// Names the input, such as `BTN_EAST`, or `ABS_HAT0X+` and `ABS_HAT0X-` for both directions of
+/// an axis.
+impl fmt::Display for ChordInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{:?}", key),
+            Self::ThresholdedAxis(t_a) => {
+                let direction = match t_a.threshold() {
+                    ThresholdType::Greater => '+',
+                    ThresholdType::Lesser => '-',
+                };
+                write!(f, "{:?}{}", t_a.code(), direction)
+            }
+        }
+    }
+}
+
 impl From<KeyCode> for ChordInput {
     fn from(key: KeyCode) -> Self {
         Self::Key(key)
@@ -3,6 +3,7 @@ use crate::events::*;
 use crate::input::events::InputEvent;
 use crate::mapping::actions::*;
 use crate::mapping::thresholds::*;
+use log::trace;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -41,6 +42,7 @@ impl ModifierMap {
                         None
                     } else {
                         // Axis swapped threshold
+                        trace!("{:?} swapped to {:?}", ev, thresholded_axis);
                         let prev_act = self
                             .modifier_mapping
                             .get(&ModifierInput::Axis((ev.axis(), prev_threshold).into()))
@@ -56,6 +58,7 @@ impl ModifierMap {
                 }
                 None => {
                     // Axis passed new threshold
+                    trace!("{:?} crossed {:?}", ev, thresholded_axis);
                     self.axis_states
                         .insert(ev.axis(), Some(thresholded_axis.threshold()));
                     self.modifier_mapping
@@ -67,6 +70,7 @@ impl ModifierMap {
             None => match prev_state {
                 Some(threshold_type) => {
                     // Axis receded from threshold
+                    trace!("{:?} receded from {:?}", ev, threshold_type);
                     self.axis_states.insert(ev.axis(), None);
                     self.modifier_mapping
                         .get(&ModifierInput::Axis((ev.axis(), threshold_type).into()))
@@ -77,6 +81,12 @@ impl ModifierMap {
             },
         }
     }
+    pub fn clear_state(&mut self) {
+        self.axis_states
+            .values_mut()
+            .for_each(|state| *state = None);
+    }
+
     pub fn handle_event(&mut self, ev: &InputEvent) -> Option<(Action, Option<Action>)> {
         match ev {
             InputEvent::KeyEvent(kev) => self.handle_key(kev),
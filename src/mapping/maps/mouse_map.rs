@@ -153,6 +153,12 @@ impl MouseMap {
         }
         */
     }
+    pub fn clear_state(&mut self) {
+        self.axis_states
+            .values_mut()
+            .for_each(|state| *state = None);
+    }
+
     pub fn handle_event(&mut self, ev: &InputEvent) -> Option<(Action, Option<Action>)> {
         match ev {
             InputEvent::KeyEvent(_) => None,
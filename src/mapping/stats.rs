@@ -0,0 +1,299 @@
+use super::actions::Action;
+use super::mapper::{ActionOrigin, ChordInput};
+use crate::input::events::InputEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often a single mapping fired.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Usage {
+    /// How many times the mapping fired.
+    pub count: u64,
+
+    /// The action the mapping resolved to most recently, as JSON.
+    pub action: String,
+
+    /// The sum of the time from first press to emit, in microseconds, over every firing. Only
+    /// chords take time to enter, so this stays 0 for other mappings.
+    #[serde(default)]
+    pub total_latency_us: u64,
+}
+
+impl Usage {
+    /// Returns the mean time from the first press of a chord until it emitted, if it ever fired.
+    pub fn mean_latency_ms(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total_latency_us as f64 / self.count as f64 / 1000.0)
+    }
+}
+
+/// The usage of every mapping within a single configuration file.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LayerUsage {
+    /// Every chord, named by its inputs such as `BTN_EAST + BTN_SOUTH`.
+    #[serde(default)]
+    pub chords: BTreeMap<String, Usage>,
+
+    /// Every modifier input, such as `BTN_TL`. Every action a modifier resolves is counted, so
+    /// holding a key down through a modifier usually counts both its press and its release.
+    #[serde(default)]
+    pub modifiers: BTreeMap<String, Usage>,
+
+    /// Every axis of the mouse mappings, such as `ABS_RX`.
+    #[serde(default)]
+    pub mouse: BTreeMap<String, Usage>,
+
+    /// How many chords without a mapping were released.
+    #[serde(default)]
+    pub unmapped_chords: u64,
+}
+
+impl LayerUsage {
+    /// Returns the chords from most to least used, breaking ties by name.
+    pub fn ranked_chords(&self) -> Vec<(&str, &Usage)> {
+        let mut chords: Vec<_> = self
+            .chords
+            .iter()
+            .map(|(name, usage)| (name.as_str(), usage))
+            .collect();
+        chords.sort_by(|(a_name, a), (b_name, b)| b.count.cmp(&a.count).then(a_name.cmp(b_name)));
+        chords
+    }
+
+    /// Returns the mean time from first press to emit over every chord that fired.
+    pub fn mean_chord_latency_ms(&self) -> Option<f64> {
+        let (count, total) = self.chords.values().fold((0, 0), |(count, total), usage| {
+            (count + usage.count, total + usage.total_latency_us)
+        });
+        (count > 0).then(|| total as f64 / count as f64 / 1000.0)
+    }
+}
+
+/// The kind of mapping a [UsageRow] is about.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum MappingKind {
+    Chord,
+    Modifier,
+    Mouse,
+}
+
+/// A single mapping of a [UsageStats], flattened for export.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub layer: PathBuf,
+    pub kind: MappingKind,
+    pub inputs: String,
+    pub action: String,
+    pub count: u64,
+    pub mean_latency_ms: Option<f64>,
+}
+
+/// How often every chord, modifier and mouse mapping fired, per configuration file.
+///
+/// The counts are kept in a JSON file across sessions. See
+/// [Configuration::stats_file](super::configuration::Configuration::stats_file).
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::mapping::actions::*;
+/// use chord2key::mapping::mapper::*;
+/// use chord2key::mapping::stats::*;
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// let layer = Path::new("/home/user/configs/letters.json");
+/// let chord = vec![ChordInput::Key(KeyCode::BTN_EAST), ChordInput::Key(KeyCode::BTN_SOUTH)];
+/// let action: Action = InnerAction::Suspend.into();
+///
+/// let mut stats = UsageStats::default();
+/// stats.register_chord(layer, &chord, &action);
+/// for latency_ms in [40, 60] {
+///     let origin = ActionOrigin::Chord(chord.clone());
+///     stats.record(layer, &origin, &action, Duration::from_millis(latency_ms));
+/// }
+/// stats.record_unmapped(layer);
+///
+/// let usage = &stats.layers[layer];
+/// assert_eq!(usage.unmapped_chords, 1);
+/// assert_eq!(usage.ranked_chords()[0].0, "BTN_EAST + BTN_SOUTH");
+/// assert_eq!(usage.ranked_chords()[0].1.count, 2);
+/// assert_eq!(usage.mean_chord_latency_ms(), Some(50.0));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// The usage of each configuration file, by its path.
+    pub layers: BTreeMap<PathBuf, LayerUsage>,
+}
+
+impl UsageStats {
+    /// Loads the statistics from a JSON file.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Loads the statistics from a JSON file, starting over if the file doesn't exist yet.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        match File::open(&path) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            _ => Self::load_from_file(path),
+        }
+    }
+
+    /// Saves the statistics to a JSON file.
+    ///
+    /// The file is replaced all at once, so it's never left half written.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        serde_json::to_writer_pretty(&File::create(&partial)?, self)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Names a chord by its inputs, in a stable order.
+    fn chord_name(chord: &[ChordInput]) -> String {
+        let mut inputs: Vec<String> = chord.iter().map(ChordInput::to_string).collect();
+        inputs.sort();
+        inputs.join(" + ")
+    }
+
+    /// Names the input that changed in an event.
+    fn event_name(ev: &InputEvent) -> String {
+        match ev {
+            InputEvent::KeyEvent(kev) => format!("{:?}", kev.key()),
+            InputEvent::AbsAxisEvent(aev) => format!("{:?}", aev.axis()),
+            InputEvent::RelAxisEvent(rev) => format!("{:?}", rev.axis()),
+        }
+    }
+
+    fn layer_mut(&mut self, layer: &Path) -> &mut LayerUsage {
+        self.layers.entry(layer.to_path_buf()).or_default()
+    }
+
+    /// Adds a mapped chord that hasn't fired yet, so it's listed among the least used chords.
+    pub fn register_chord(&mut self, layer: &Path, chord: &[ChordInput], action: &Action) {
+        let usage = self
+            .layer_mut(layer)
+            .chords
+            .entry(Self::chord_name(chord))
+            .or_default();
+        usage.action = serde_json::to_string(action).unwrap_or_default();
+    }
+
+    /// Counts a resolved action. Only chords, modifiers and mouse mappings are counted.
+    ///
+    /// The latency is the time from the first press of a chord until it emitted, and is ignored
+    /// for other mappings.
+    pub fn record(
+        &mut self,
+        layer: &Path,
+        origin: &ActionOrigin,
+        action: &Action,
+        latency: Duration,
+    ) {
+        let layer = self.layer_mut(layer);
+        let usage = match origin {
+            ActionOrigin::Chord(chord) => {
+                let usage = layer.chords.entry(Self::chord_name(chord)).or_default();
+                usage.total_latency_us += latency.as_micros() as u64;
+                usage
+            }
+            ActionOrigin::Modifier(ev) => layer.modifiers.entry(Self::event_name(ev)).or_default(),
+            ActionOrigin::Mouse(ev) => layer.mouse.entry(Self::event_name(ev)).or_default(),
+            ActionOrigin::UnmappedChord(..) | ActionOrigin::Injected => return,
+        };
+        usage.count += 1;
+        usage.action = serde_json::to_string(action).unwrap_or_default();
+    }
+
+    /// Counts a released chord without a mapping.
+    pub fn record_unmapped(&mut self, layer: &Path) {
+        self.layer_mut(layer).unmapped_chords += 1;
+    }
+
+    /// Returns every mapping of every layer as a flat list, such as for exporting to JSON.
+    pub fn rows(&self) -> Vec<UsageRow> {
+        let mut rows = Vec::new();
+        for (layer, usage) in &self.layers {
+            let kinds = [
+                (MappingKind::Chord, &usage.chords),
+                (MappingKind::Modifier, &usage.modifiers),
+                (MappingKind::Mouse, &usage.mouse),
+            ];
+            for (kind, mappings) in kinds {
+                rows.extend(mappings.iter().map(|(inputs, usage)| UsageRow {
+                    layer: layer.clone(),
+                    kind,
+                    inputs: inputs.clone(),
+                    action: usage.action.clone(),
+                    count: usage.count,
+                    mean_latency_ms: match kind {
+                        MappingKind::Chord => usage.mean_latency_ms(),
+                        _ => None,
+                    },
+                }));
+            }
+        }
+        rows
+    }
+
+    /// Returns every mapping of every layer as CSV, with a header line.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::constants::*;
+    /// use chord2key::mapping::actions::*;
+    /// use chord2key::mapping::mapper::*;
+    /// use chord2key::mapping::stats::*;
+    /// use std::path::Path;
+    ///
+    /// let mut stats = UsageStats::default();
+    /// let chord = [ChordInput::Key(KeyCode::BTN_EAST)];
+    /// stats.register_chord(Path::new("letters.json"), &chord, &InnerAction::Suspend.into());
+    ///
+    /// let csv = stats.to_csv();
+    /// let mut lines = csv.lines();
+    /// assert_eq!(
+    ///     lines.next(),
+    ///     Some("layer,kind,inputs,action,count,mean_latency_ms")
+    /// );
+    /// assert_eq!(
+    ///     lines.next(),
+    ///     Some(r#"letters.json,Chord,BTN_EAST,"{""InnerAction"":""Suspend""}",0,"#)
+    /// );
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("layer,kind,inputs,action,count,mean_latency_ms\n");
+        for row in self.rows() {
+            let fields = [
+                row.layer.display().to_string(),
+                format!("{:?}", row.kind),
+                row.inputs,
+                row.action,
+                row.count.to_string(),
+                row.mean_latency_ms
+                    .map(|ms| format!("{:.1}", ms))
+                    .unwrap_or_default(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
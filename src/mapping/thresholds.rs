@@ -363,8 +363,7 @@ impl AllAxisThresholds {
     pub fn get_passing(&self, axis_event: &AbsAxisEvent) -> Option<ThresholdedAxis> {
         self.map
             .get(&axis_event.axis())
-            .map(|thresholds| thresholds.get_passing(axis_event))
-            .flatten()
+            .and_then(|thresholds| thresholds.get_passing(axis_event))
     }
 
     /// Returns the passing [ThresholdedAxis] input from the [AbsAxisEvent], if any, along with the
@@ -407,8 +406,7 @@ impl AllAxisThresholds {
     ) -> Option<(ThresholdedAxis, AxisState)> {
         self.map
             .get(&axis_event.axis())
-            .map(|thresholds| thresholds.get_passing_with_state(axis_event))
-            .flatten()
+            .and_then(|thresholds| thresholds.get_passing_with_state(axis_event))
     }
 
     /// Returns if the axis within the [AbsAxisEvent] has a stored threshold
@@ -454,7 +452,7 @@ impl AllAxisThresholds {
     /// assert_eq!(test_codes, codes);
     /// ```
     pub fn codes(&self) -> impl Iterator<Item = &AbsAxisCode> {
-        self.map.iter().map(|(key, _)| key)
+        self.map.keys()
     }
 }
 
@@ -0,0 +1,457 @@
+use super::configuration::Configuration;
+use super::maps::chord_map::{ChordInput, ChordMap};
+use super::thresholds::AllAxisThresholds;
+use crate::input::events::InputEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// A character the trainee can be asked to type, along with the chord that types it.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TrainingTarget {
+    pub text: char,
+    pub chord: Vec<ChordInput>,
+}
+
+/// How well a single character has been typed so far.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CharProgress {
+    /// How many chords were entered while the character was expected.
+    pub attempts: u64,
+
+    /// How many of those chords typed the character.
+    pub hits: u64,
+
+    /// The sum of the time from being asked for the character until typing it, in milliseconds,
+    /// over every hit.
+    pub total_hit_ms: u64,
+}
+
+impl CharProgress {
+    /// Returns the fraction of attempts that typed the character, if there were any.
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.attempts > 0).then(|| self.hits as f64 / self.attempts as f64)
+    }
+
+    /// Returns the mean time until the character was typed, if it ever was.
+    pub fn mean_hit_ms(&self) -> Option<f64> {
+        (self.hits > 0).then(|| self.total_hit_ms as f64 / self.hits as f64)
+    }
+}
+
+/// The outcome of entering a chord while training.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attempt {
+    /// The chord typed the expected character.
+    Hit {
+        target: char,
+
+        /// The time from being asked for the character until typing it.
+        time: Duration,
+    },
+
+    /// The chord typed something else, or nothing at all.
+    Miss {
+        target: char,
+
+        /// The chord that types the expected character.
+        expected: Vec<ChordInput>,
+
+        /// The chord that was entered.
+        entered: Vec<ChordInput>,
+
+        /// The character the entered chord types, if any.
+        typed: Option<char>,
+    },
+}
+
+/// A typing tutor for the chords of a configuration.
+///
+/// The trainee is asked for one character at a time, or for whole words if any are given. Chords
+/// are read the same way as while mapping, but nothing is emitted. A missed character has to be
+/// typed again before moving on. The characters typed least accurately or most slowly so far are
+/// asked for most often.
+///
+/// Only chords mapped to a [Pulse](crate::output::actions::Pulse) that types a single character
+/// can be trained. If several chords type the same character, the one with the fewest inputs is
+/// expected.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::events::*;
+/// use chord2key::input::events::InputEvent;
+/// use chord2key::mapping::configuration::*;
+/// use chord2key::mapping::mapper::*;
+/// use chord2key::mapping::training::*;
+/// use std::time::{Duration, Instant};
+///
+/// let config = Configuration::load_from_file(
+///     "configs/nintendo_pro_controller/pro_keyboard.json"
+/// ).unwrap();
+/// let mut trainer = Trainer::new(config, 7).unwrap().with_words(["gate"]);
+/// assert_eq!(trainer.prompt(), &['g', 'a', 't', 'e']);
+///
+/// // Enter the chord of the expected character
+/// let target = trainer.current().clone();
+/// let start = Instant::now();
+/// let mut attempt = None;
+/// for state in [PressState::Down, PressState::Up] {
+///     for input in &target.chord {
+///         if let ChordInput::Key(key) = input {
+///             let ev = InputEvent::KeyEvent(KeyEvent::new(*key, state));
+///             attempt = attempt.or(trainer.handle_event(&ev, start + Duration::from_secs(1)));
+///         }
+///     }
+/// }
+///
+/// assert!(matches!(attempt, Some(Attempt::Hit { target: 'g', .. })));
+/// assert_eq!(trainer.position(), 1);
+/// assert_eq!(trainer.progress()[&'g'].hits, 1);
+/// ```
+pub struct Trainer {
+    chords: ChordMap,
+    targets: Vec<TrainingTarget>,
+    words: Vec<Vec<char>>,
+    progress: BTreeMap<char, CharProgress>,
+    prompt: Vec<char>,
+    position: usize,
+
+    /// When the current character was asked for, or when the previous attempt at it was made.
+    shown: Instant,
+
+    /// The state of the random number generator that picks the prompts.
+    rng: u64,
+}
+
+impl Trainer {
+    /// How much more the error rate of a character weighs than its slowness when picking what to
+    /// ask for.
+    const ERROR_WEIGHT: f64 = 4.0;
+
+    /// Creates a trainer for the chords of the configuration, asking for single characters.
+    ///
+    /// The seed picks the sequence of prompts. Returns an error if no chord types a character.
+    pub fn new(config: Configuration, seed: u64) -> Result<Self, Box<dyn Error>> {
+        let mut best = BTreeMap::<char, Vec<ChordInput>>::new();
+        for (chord, action) in &config.chord_mapping {
+            if let Some(text) = action.typed_char() {
+                let expected = best.entry(text).or_insert_with(|| chord.clone());
+                if chord.len() < expected.len() {
+                    *expected = chord.clone();
+                }
+            }
+        }
+        if best.is_empty() {
+            return Err("No chord of the configuration types a character".into());
+        }
+        let targets = best
+            .into_iter()
+            .map(|(text, chord)| TrainingTarget { text, chord })
+            .collect();
+
+        let thresholds = AllAxisThresholds::init(config.axis_thresholds);
+        let mut trainer = Self {
+            chords: ChordMap::init(config.chord_mapping, thresholds),
+            targets,
+            words: Vec::new(),
+            progress: BTreeMap::new(),
+            prompt: Vec::new(),
+            position: 0,
+            shown: Instant::now(),
+            // Xorshift gets stuck on 0
+            rng: seed | 1,
+        };
+        trainer.next_prompt();
+        Ok(trainer)
+    }
+
+    /// Asks for whole words instead of single characters.
+    ///
+    /// Words containing a character that no chord types are left out. If none are left, single
+    /// characters are still asked for.
+    pub fn with_words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.words = words
+            .into_iter()
+            .map(|word| word.as_ref().chars().collect::<Vec<_>>())
+            .filter(|word| !word.is_empty() && word.iter().all(|c| self.target(*c).is_some()))
+            .collect();
+        self.next_prompt();
+        self
+    }
+
+    /// Returns every character that can be trained, in order.
+    pub fn targets(&self) -> &[TrainingTarget] {
+        &self.targets
+    }
+
+    /// Returns the number of words that are asked for.
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns the characters currently asked for.
+    pub fn prompt(&self) -> &[char] {
+        &self.prompt
+    }
+
+    /// Returns how many characters of the prompt were already typed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the character expected next.
+    pub fn current(&self) -> &TrainingTarget {
+        // Every prompt is made of targets
+        self.target(self.prompt[self.position]).unwrap()
+    }
+
+    /// Returns the progress of every character that was asked for so far.
+    pub fn progress(&self) -> &BTreeMap<char, CharProgress> {
+        &self.progress
+    }
+
+    /// Returns every character that was asked for so far, starting with the one that most needs
+    /// practice.
+    pub fn weakest(&self) -> Vec<char> {
+        let mut chars: Vec<char> = self.progress.keys().copied().collect();
+        chars.sort_by(|a, b| self.weight(*b).total_cmp(&self.weight(*a)));
+        chars
+    }
+
+    fn target(&self, text: char) -> Option<&TrainingTarget> {
+        self.targets
+            .binary_search_by_key(&text, |target| target.text)
+            .ok()
+            .map(|i| &self.targets[i])
+    }
+
+    /// Handles an input event at the given time, returning the outcome once a chord is entered.
+    pub fn handle_event(&mut self, ev: &InputEvent, now: Instant) -> Option<Attempt> {
+        let (entered, typed) = match self.chords.handle_event(ev, now) {
+            Some(action) => (self.chords.get_emitted_chord(), action.typed_char()),
+            None => (self.chords.take_unmapped()?, None),
+        };
+
+        let target = self.current().clone();
+        let time = now.saturating_duration_since(self.shown);
+        self.shown = now;
+
+        let progress = self.progress.entry(target.text).or_default();
+        progress.attempts += 1;
+        if typed != Some(target.text) {
+            return Some(Attempt::Miss {
+                target: target.text,
+                expected: target.chord,
+                entered,
+                typed,
+            });
+        }
+
+        progress.hits += 1;
+        progress.total_hit_ms += time.as_millis() as u64;
+        self.position += 1;
+        if self.position == self.prompt.len() {
+            self.next_prompt();
+        }
+        Some(Attempt::Hit {
+            target: target.text,
+            time,
+        })
+    }
+
+    /// Forgets the partially entered chord, such as after an input device disconnects.
+    pub fn clear_chord(&mut self) {
+        self.chords.clear_state();
+    }
+
+    /// Returns how much a character needs practice, from its error rate and how much slower it is
+    /// typed than the others.
+    fn weight(&self, text: char) -> f64 {
+        let progress = self.progress.get(&text).cloned().unwrap_or_default();
+
+        // Laplace smoothing starts untried characters at an error rate of one half
+        let misses = progress.attempts - progress.hits;
+        let error = (misses + 1) as f64 / (progress.attempts + 2) as f64;
+
+        let (hits, total_ms) = self.progress.values().fold((0, 0), |(hits, total), p| {
+            (hits + p.hits, total + p.total_hit_ms)
+        });
+        let slowness = match progress.mean_hit_ms() {
+            Some(mean) if total_ms > 0 => mean / (total_ms as f64 / hits as f64),
+            _ => 1.0,
+        };
+
+        // Squared so the weakest characters stand out from the many mastered ones
+        (Self::ERROR_WEIGHT * error + slowness).powi(2)
+    }
+
+    /// Returns a random number from 0 up to 1.
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Picks an index at random, favouring the higher weights.
+    fn pick(&mut self, weights: &[f64]) -> usize {
+        let mut left = self.random() * weights.iter().sum::<f64>();
+        for (i, weight) in weights.iter().enumerate() {
+            if left < *weight {
+                return i;
+            }
+            left -= weight;
+        }
+        weights.len() - 1
+    }
+
+    /// Asks for the next word or character, avoiding the current one if there is a choice.
+    fn next_prompt(&mut self) {
+        let previous = std::mem::take(&mut self.prompt);
+        let candidates: Vec<Vec<char>> = if self.words.is_empty() {
+            self.targets
+                .iter()
+                .map(|target| vec![target.text])
+                .collect()
+        } else {
+            self.words.clone()
+        };
+
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|candidate| {
+                if *candidate == previous && candidates.len() > 1 {
+                    0.0
+                } else {
+                    let total: f64 = candidate.iter().map(|c| self.weight(*c)).sum();
+                    total / candidate.len() as f64
+                }
+            })
+            .collect();
+
+        let i = self.pick(&weights);
+        self.prompt = candidates[i].clone();
+        self.position = 0;
+        self.shown = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::events::*;
+
+    const PRO_KEYBOARD: &str = "configs/nintendo_pro_controller/pro_keyboard.json";
+
+    fn trainer() -> Trainer {
+        Trainer::new(Configuration::load_from_file(PRO_KEYBOARD).unwrap(), 42).unwrap()
+    }
+
+    /// Enters a chord of buttons, returning the outcome.
+    fn enter(trainer: &mut Trainer, keys: &[KeyCode], now: Instant) -> Option<Attempt> {
+        let mut attempt = None;
+        for state in [PressState::Down, PressState::Up] {
+            for key in keys {
+                let ev = InputEvent::KeyEvent(KeyEvent::new(*key, state));
+                attempt = attempt.or(trainer.handle_event(&ev, now));
+            }
+        }
+        attempt
+    }
+
+    fn keys_of(target: &TrainingTarget) -> Vec<KeyCode> {
+        target
+            .chord
+            .iter()
+            .map(|input| match input {
+                ChordInput::Key(key) => *key,
+                other => panic!("{} isn't a button", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn misses_show_the_expected_chord_and_are_asked_again() {
+        // Both letters are typed with buttons alone
+        let mut trainer = trainer().with_words(["fg"]);
+        let f = trainer.current().clone();
+        let g = trainer.target('g').unwrap().clone();
+        let now = Instant::now() + Duration::from_secs(1);
+
+        match enter(&mut trainer, &keys_of(&g), now) {
+            Some(Attempt::Miss {
+                target,
+                expected,
+                typed,
+                ..
+            }) => {
+                assert_eq!(target, 'f');
+                assert_eq!(expected, f.chord);
+                assert_eq!(typed, Some('g'));
+            }
+            other => panic!("Expected a miss, got {:?}", other),
+        }
+        assert_eq!(trainer.position(), 0);
+
+        // An unmapped chord is a miss that types nothing. Releasing A first then leaves Y, which
+        // types another letter and misses too
+        match enter(&mut trainer, &[KeyCode::BTN_EAST, KeyCode::BTN_WEST], now) {
+            Some(Attempt::Miss { typed: None, .. }) => (),
+            other => panic!("Expected a miss, got {:?}", other),
+        }
+
+        assert!(matches!(
+            enter(&mut trainer, &keys_of(&f), now),
+            Some(Attempt::Hit { target: 'f', .. })
+        ));
+        assert!(matches!(
+            enter(&mut trainer, &keys_of(&g), now),
+            Some(Attempt::Hit { target: 'g', .. })
+        ));
+
+        // The only word is asked for again
+        assert_eq!(trainer.prompt(), &['f', 'g']);
+        assert_eq!(trainer.position(), 0);
+        assert_eq!(trainer.progress()[&'f'].attempts, 4);
+        assert_eq!(trainer.progress()[&'f'].accuracy(), Some(1.0 / 4.0));
+        assert_eq!(trainer.weakest(), vec!['f', 'g']);
+    }
+
+    #[test]
+    fn weak_characters_are_asked_for_more_often() {
+        let mut trainer = trainer();
+        trainer.progress.insert(
+            'q',
+            CharProgress {
+                attempts: 20,
+                hits: 5,
+                total_hit_ms: 5000,
+            },
+        );
+        for target in trainer.targets.clone() {
+            if target.text != 'q' {
+                let progress = trainer.progress.entry(target.text).or_default();
+                progress.attempts = 20;
+                progress.hits = 20;
+                progress.total_hit_ms = 20 * 500;
+            }
+        }
+
+        let mut asked = 0;
+        for _ in 0..1000 {
+            trainer.next_prompt();
+            if trainer.prompt() == ['q'] {
+                asked += 1;
+            }
+        }
+        // Evenly, each character would be asked for about 1000 / 45 times
+        assert!(asked > 100, "q was only asked for {} times", asked);
+    }
+}
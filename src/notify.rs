@@ -0,0 +1,373 @@
+use crate::mapping::mapper::MapperEvent;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// Something that announces [MapperEvent]s to the user, such as layer changes.
+///
+/// Notifiers are given every event, including [MapperEvent::ActionResolved], but the ones in this
+/// module only announce the events that [describe] has a description for.
+pub trait Notifier {
+    /// Announces the event. An error is logged, but doesn't stop the mapping.
+    fn notify(&mut self, event: &MapperEvent) -> Result<(), Box<dyn Error>>;
+}
+
+/// The notifiers that can be chosen from a [Configuration](crate::mapping::configuration::Configuration).
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum NotifierConfig {
+    /// Print every announced event to stdout. See [LineNotifier].
+    Stdout,
+
+    /// Run a program, given as the program followed by its arguments, for every announced event.
+    /// See [ExecNotifier].
+    Exec(Vec<String>),
+
+    /// Show a desktop notification for every announced event. See [DesktopNotifier].
+    Desktop,
+}
+
+impl NotifierConfig {
+    /// Creates the configured notifier.
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self {
+            Self::Stdout => Box::new(LineNotifier::new(std::io::stdout())),
+            Self::Exec(command) => Box::new(ExecNotifier::new(command.clone())),
+            Self::Desktop => Box::new(DesktopNotifier::new()),
+        }
+    }
+}
+
+/// Returns a short description of an event for the user, or None if it isn't worth announcing.
+///
+/// # Example
+/// ```
+/// use chord2key::mapping::mapper::MapperEvent;
+/// use chord2key::notify::*;
+///
+/// let event = MapperEvent::LayerChanged {
+///     layer: "/home/user/configs/letters.json".into(),
+///     name: "Letters".to_string(),
+/// };
+/// assert_eq!(describe(&event), Some("Layer: Letters".to_string()));
+///
+/// let event = MapperEvent::SuspendChanged { suspended: true };
+/// assert_eq!(describe(&event), Some("Mapping suspended".to_string()));
+/// ```
+pub fn describe(event: &MapperEvent) -> Option<String> {
+    match event {
+        MapperEvent::ActionResolved { .. } | MapperEvent::ChordUnmapped { .. } => None,
+        MapperEvent::LayerChanged { name, .. } => Some(format!("Layer: {}", name)),
+        MapperEvent::SuspendChanged { suspended: true } => Some("Mapping suspended".to_string()),
+        MapperEvent::SuspendChanged { suspended: false } => Some("Mapping resumed".to_string()),
+        MapperEvent::Error { message } => Some(format!("Error: {}", message)),
+    }
+}
+
+/// A [Notifier] that writes every announced event as a single JSON line, such as
+/// `{"LayerChanged":{"layer":"/home/user/configs/letters.json","name":"Letters"}}`.
+///
+/// # Example
+/// ```
+/// use chord2key::mapping::mapper::MapperEvent;
+/// use chord2key::notify::*;
+///
+/// let mut notifier = LineNotifier::new(Vec::new());
+/// notifier
+///     .notify(&MapperEvent::SuspendChanged { suspended: true })
+///     .unwrap();
+///
+/// let lines = String::from_utf8(notifier.into_inner()).unwrap();
+/// assert_eq!(lines, "{\"SuspendChanged\":{\"suspended\":true}}\n");
+/// ```
+pub struct LineNotifier<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> LineNotifier<W> {
+    /// Creates a notifier writing to the given writer, such as stdout.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Notifier for LineNotifier<W> {
+    fn notify(&mut self, event: &MapperEvent) -> Result<(), Box<dyn Error>> {
+        if describe(event).is_none() {
+            return Ok(());
+        }
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A [Notifier] that runs a program for every announced event, without waiting for it to finish.
+///
+/// The event is described through environment variables:
+/// - `CHORD2KEY_EVENT`: one of `layer`, `suspend`, `resume` or `error`
+/// - `CHORD2KEY_MESSAGE`: the description from [describe]
+/// - `CHORD2KEY_LAYER` and `CHORD2KEY_LAYER_PATH`: the name and file of the new layer, for
+///   layer events
+pub struct ExecNotifier {
+    command: Vec<String>,
+
+    /// Programs that were started, but haven't been waited on yet.
+    running: Vec<Child>,
+}
+
+impl ExecNotifier {
+    /// Creates a notifier running the given program followed by its arguments.
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            command,
+            running: Vec::new(),
+        }
+    }
+}
+
+impl Notifier for ExecNotifier {
+    fn notify(&mut self, event: &MapperEvent) -> Result<(), Box<dyn Error>> {
+        // Clean up after programs that finished since the last event
+        self.running
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+        let message = match describe(event) {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or("The exec notifier has no program")?;
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .env("CHORD2KEY_MESSAGE", message);
+        match event {
+            MapperEvent::LayerChanged { layer, name } => {
+                command
+                    .env("CHORD2KEY_EVENT", "layer")
+                    .env("CHORD2KEY_LAYER", name)
+                    .env("CHORD2KEY_LAYER_PATH", layer);
+            }
+            MapperEvent::SuspendChanged { suspended } => {
+                command.env(
+                    "CHORD2KEY_EVENT",
+                    if *suspended { "suspend" } else { "resume" },
+                );
+            }
+            MapperEvent::Error { .. } => {
+                command.env("CHORD2KEY_EVENT", "error");
+            }
+            MapperEvent::ActionResolved { .. } | MapperEvent::ChordUnmapped { .. } => {}
+        }
+        self.running.push(command.spawn()?);
+        Ok(())
+    }
+}
+
+/// A [Notifier] that shows a desktop notification through the `org.freedesktop.Notifications`
+/// D-Bus service on the session bus.
+///
+/// The notifications are sent with the `gdbus` program on a separate thread, so a slow or missing
+/// notification service never delays the mapping. Each notification replaces the previous one, so
+/// quickly switching layers doesn't pile them up. When running with `sudo`, the session bus is
+/// only found if `DBUS_SESSION_BUS_ADDRESS` is kept, such as with `sudo -E`.
+pub struct DesktopNotifier {
+    message_tx: Sender<String>,
+}
+
+impl DesktopNotifier {
+    /// How long each notification is shown, in milliseconds.
+    pub const TIMEOUT_MS: i32 = 3000;
+
+    /// Creates a notifier that sends its notifications with `gdbus`.
+    pub fn new() -> Self {
+        Self::with_program("gdbus")
+    }
+
+    /// Creates a notifier that sends its notifications with the given `gdbus` compatible program.
+    pub fn with_program(program: &str) -> Self {
+        let program = program.to_string();
+        let (message_tx, message_rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let mut replaces_id = 0;
+            for message in message_rx {
+                match Self::send(&program, replaces_id, &message) {
+                    Ok(id) => replaces_id = id,
+                    Err(e) => warn!("Could not show a desktop notification: {}", e),
+                }
+            }
+        });
+        Self { message_tx }
+    }
+
+    /// Sends a single notification, returning its id.
+    fn send(program: &str, replaces_id: u32, message: &str) -> Result<u32, Box<dyn Error>> {
+        let output = Command::new(program)
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.Notifications",
+                "--object-path",
+                "/org/freedesktop/Notifications",
+                "--method",
+                "org.freedesktop.Notifications.Notify",
+                "\"chord2key\"",
+                &format!("uint32 {}", replaces_id),
+                "\"\"",
+                &gvariant_string(message),
+                "\"\"",
+                "@as []",
+                "@a{sv} {}",
+                &format!("int32 {}", Self::TIMEOUT_MS),
+            ])
+            .stdin(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+
+        // The reply looks like "(uint32 7,)"
+        let reply = String::from_utf8_lossy(&output.stdout);
+        let id = reply
+            .split_whitespace()
+            .nth(1)
+            .map(|id| id.trim_end_matches(|c: char| !c.is_ascii_digit()))
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(0);
+        Ok(id)
+    }
+}
+
+impl Default for DesktopNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&mut self, event: &MapperEvent) -> Result<(), Box<dyn Error>> {
+        if let Some(message) = describe(event) {
+            self.message_tx
+                .send(message)
+                .map_err(|_| "The desktop notification thread has stopped")?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a string in the GVariant text format used by `gdbus`.
+fn gvariant_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::actions::InnerAction;
+    use crate::mapping::mapper::ActionOrigin;
+    use crate::test_util::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn layer_changed(name: &str) -> MapperEvent {
+        MapperEvent::LayerChanged {
+            layer: "/home/user/configs/letters.json".into(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Waits up to a few seconds for the file to hold the given number of lines.
+    fn wait_for_lines(path: &Path, count: usize) -> Vec<String> {
+        let start = Instant::now();
+        loop {
+            let text = std::fs::read_to_string(path).unwrap_or_default();
+            let lines: Vec<String> = text.lines().map(str::to_string).collect();
+            if lines.len() >= count || start.elapsed() > Duration::from_secs(5) {
+                return lines;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn exec_describes_the_event_in_the_environment() {
+        let dir = TempDir::new("exec");
+        let out = dir.join("out");
+        let script = r#"printf '%s|%s|%s|%s\n' "$CHORD2KEY_EVENT" "$CHORD2KEY_MESSAGE" \
+            "$CHORD2KEY_LAYER" "$CHORD2KEY_LAYER_PATH" >> "$0""#;
+        let mut notifier = ExecNotifier::new(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            script.to_string(),
+            out.to_str().unwrap().to_string(),
+        ]);
+        let mut notify = |event: MapperEvent| {
+            notifier.notify(&event).unwrap();
+            for mut child in notifier.running.drain(..) {
+                child.wait().unwrap();
+            }
+        };
+
+        notify(layer_changed("Letters"));
+        notify(MapperEvent::SuspendChanged { suspended: true });
+        notify(MapperEvent::Error {
+            message: "Disconnected".to_string(),
+        });
+        // Resolved actions aren't announced, so nothing runs for them
+        notify(MapperEvent::ActionResolved {
+            action: InnerAction::Suspend.into(),
+            origin: ActionOrigin::Injected,
+            layer: "/home/user/configs/letters.json".into(),
+        });
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "layer|Layer: Letters|Letters|/home/user/configs/letters.json\n\
+             suspend|Mapping suspended||\n\
+             error|Error: Disconnected||\n"
+        );
+    }
+
+    #[test]
+    fn desktop_quotes_the_message_and_replaces_the_previous_one() {
+        let dir = TempDir::new("desktop");
+        let stub = dir.join("gdbus");
+        let calls = dir.join("calls");
+        std::fs::write(
+            &stub,
+            format!(
+                "#!/bin/sh\nIFS='|'\nprintf '%s\\n' \"$*\" >> '{}'\necho '(uint32 42,)'\n",
+                calls.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut notifier = DesktopNotifier::with_program(stub.to_str().unwrap());
+        notifier.notify(&layer_changed(r#"Say "hi" \o/"#)).unwrap();
+        notifier.notify(&layer_changed("Numbers")).unwrap();
+
+        let lines = wait_for_lines(&calls, 2);
+        assert_eq!(lines.len(), 2);
+        // Every argument stands on its own, and the second call replaces the first notification
+        assert!(lines[0].starts_with("call|--session|"));
+        assert!(lines[0].contains(r#"|uint32 0|""|"Layer: Say \"hi\" \\o/"|""|"#));
+        assert!(lines[1].contains(r#"|uint32 42|""|"Layer: Numbers"|""|"#));
+    }
+}
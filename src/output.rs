@@ -1,2 +1,7 @@
 pub mod actions;
 pub mod device;
+pub mod leds;
+pub mod passthrough;
+pub mod rumble;
+pub mod sink;
+pub mod text;
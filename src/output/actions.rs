@@ -23,6 +23,47 @@ pub enum OutputActionType {
     Toggle,
 }
 
+impl OutputAction {
+    /// Returns the action that undoes this one, if there is one.
+    ///
+    /// A Pulse that types a character is undone by a backspace, a StateChange by its
+    /// [inverse](StateChange::inverse), and a Toggle by toggling again. Other Pulses, such as
+    /// shortcuts or mouse motion, can't be undone.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::constants::*;
+    /// use chord2key::output::actions::*;
+    ///
+    /// let typed = OutputAction::Pulse(Pulse::new(Some(vec![KeyCode::KEY_A]), None));
+    /// assert_eq!(
+    ///     typed.undo(),
+    ///     Some(OutputAction::Pulse(Pulse::new(Some(vec![KeyCode::KEY_BACKSPACE]), None)))
+    /// );
+    ///
+    /// let toggle = OutputAction::Toggle(Toggle::new(Some(vec![KeyCode::KEY_LEFTCTRL]), None));
+    /// assert_eq!(toggle.undo(), Some(toggle.clone()));
+    ///
+    /// let shortcut = Pulse::new(Some(vec![KeyCode::KEY_LEFTCTRL, KeyCode::KEY_C]), None);
+    /// assert_eq!(OutputAction::Pulse(shortcut).undo(), None);
+    /// ```
+    pub fn undo(&self) -> Option<OutputAction> {
+        match self {
+            OutputAction::Pulse(Pulse {
+                keys: Some(keys), ..
+            }) => crate::output::text::keys_to_char(keys)
+                .map(|_| OutputAction::Pulse(Pulse::new(Some(vec![KeyCode::KEY_BACKSPACE]), None))),
+            OutputAction::Pulse(_) => None,
+            OutputAction::StateChange(change) => {
+                let mut change = change.clone();
+                change.inverse();
+                Some(OutputAction::StateChange(change))
+            }
+            OutputAction::Toggle(toggle) => Some(OutputAction::Toggle(toggle.clone())),
+        }
+    }
+}
+
 /// A Toggle output event.
 ///
 /// Equivalent to pressing the key if it's not pressed, or letting go of a key if it is pressed.
@@ -1,24 +1,68 @@
 use crate::constants::*;
 use crate::output::actions::*;
+use crate::output::sink::*;
 use crate::strum::IntoEnumIterator;
-use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
-use std::collections::HashMap;
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::result::Result;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 /// A virtual device used to send keyboard and mouse output events.
 ///
+/// The events are emitted to an [OutputSink], which is a uinput virtual device unless created
+/// through [OutputDevice::with_sink].
+///
 /// Creates a new thread to handle events. Events are still executed as soon as possible as they
 /// come in, but the new thread allows for state changes on the relative axes. The state of the
 /// axis represents its velocity, and that velocity input is continously sent to the OS in a regular
 /// time interval reported by [OutputDevice::pulse_time].
 ///
-/// The created thread has the same lifetime as the struct.
+/// The created thread has the same lifetime as the struct. When the last clone is dropped, or
+/// [OutputDevice::shutdown] is called, the thread releases all held keys and mouse motion before
+/// exiting.
 pub struct OutputDevice {
-    event_tx: Sender<OutputAction>,
+    event_tx: Sender<OutputMessage>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+/// A snapshot of what an [OutputDevice] is currently holding.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct OutputState {
+    /// Every key that is currently held down, in order.
+    pub held_keys: Vec<KeyCode>,
+
+    /// The velocity of every moving relative axis, in order.
+    pub rel_axes: Vec<(RelAxisCode, AxisState)>,
+}
+
+/// The messages sent from an [OutputDevice] to its output thread.
+enum OutputMessage {
+    /// Execute an [OutputAction], which was sent at the given time.
+    Action(OutputAction, Instant),
+
+    /// Enable the absolute pointer, replying with whether it succeeded.
+    EnableAbsPointer(Sender<std::io::Result<()>>),
+
+    /// Move the absolute pointer to the given position.
+    AbsPointerMove(AxisState, AxisState),
+
+    /// Set the longest time a key may be held before the watchdog releases it.
+    SetKeyHoldLimit(Option<Duration>),
+
+    /// Reply once every earlier message has been handled.
+    Flush(Sender<()>),
+
+    /// Reply with the current [OutputState].
+    QueryState(Sender<OutputState>),
+
+    /// Release all held keys and mouse motion, then stop the thread.
+    Shutdown,
 }
 
 impl OutputDevice {
@@ -30,23 +74,49 @@ impl OutputDevice {
     /// # Example
     /// ```
     /// use chord2key::output::device::OutputDevice;
-    /// use std::time::Duration;
+    /// use std::time::{Duration, Instant};
     /// assert_eq!(OutputDevice::pulse_time(), Duration::from_millis(20));
     /// ```
     pub const fn pulse_time() -> Duration {
         Duration::from_millis(20)
     }
 
+    /// The maximum value of both axes of the absolute pointer device.
+    ///
+    /// The absolute pointer spans the entire screen, so a position of 0 is the top/left edge and a
+    /// position of this value is the bottom/right edge.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::output::device::OutputDevice;
+    /// assert_eq!(OutputDevice::abs_pointer_max(), 65535);
+    /// ```
+    pub const fn abs_pointer_max() -> AxisState {
+        65535
+    }
+
     /// Creates a new output device
     ///
     /// Returns Ok([OutputDevice]) if successful. If it could not be created, it is likely that the
     /// program was not run with sufficient permissions.
     pub fn init() -> Result<Self, std::io::Error> {
-        let mut output = VirtualOutput::init()?;
-        let (tx, rx): (Sender<OutputAction>, Receiver<OutputAction>) = mpsc::channel();
-        let _handle = thread::spawn(move || {
+        Ok(Self::with_sink(Box::new(UinputSink::init()?)))
+    }
+
+    /// Creates a new output device that emits its events to the given sink.
+    ///
+    /// See [RecordingSink] for a sink that doesn't need any OS permissions.
+    pub fn with_sink(sink: Box<dyn OutputSink>) -> Self {
+        let mut output = VirtualOutput::init(sink);
+        let (tx, rx): (Sender<OutputMessage>, Receiver<OutputMessage>) = mpsc::channel();
+        let handle = thread::spawn(move || {
             let mut start = std::time::Instant::now();
+            let mut key_hold_limit: Option<Duration> = None;
             loop {
+                if let Some(limit) = key_hold_limit {
+                    output.release_stuck_keys(limit);
+                }
+
                 let elapsed = start.elapsed();
                 let diff = OutputDevice::pulse_time().checked_sub(elapsed);
                 let diff = match diff {
@@ -62,25 +132,144 @@ impl OutputDevice {
                 };
                 let event_res = rx.recv_timeout(diff);
                 match event_res {
-                    Ok(event) => {
+                    Ok(OutputMessage::Action(event, sent)) => {
                         output.execute_event(&event);
+                        trace!(
+                            "Emitted {:?}, {}us after it was sent",
+                            event,
+                            sent.elapsed().as_micros()
+                        );
+                    }
+                    Ok(OutputMessage::EnableAbsPointer(reply_tx)) => {
+                        reply_tx.send(output.device.enable_abs_pointer()).ok();
+                    }
+                    Ok(OutputMessage::Flush(reply_tx)) => {
+                        reply_tx.send(()).ok();
+                    }
+                    Ok(OutputMessage::QueryState(reply_tx)) => {
+                        reply_tx.send(output.state()).ok();
+                    }
+                    Ok(OutputMessage::AbsPointerMove(x, y)) => {
+                        output.abs_pointer_move(x, y);
+                    }
+                    Ok(OutputMessage::SetKeyHoldLimit(limit)) => {
+                        key_hold_limit = limit;
                     }
-                    Err(e) => {
-                        if e == RecvTimeoutError::Disconnected {
-                            break;
-                        }
+                    Ok(OutputMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                        output.release_all();
+                        break;
                     }
+                    Err(RecvTimeoutError::Timeout) => {}
                 }
             }
         });
-        Ok(Self { event_tx: tx })
+        Self {
+            event_tx: tx,
+            thread: Arc::new(Mutex::new(Some(handle))),
+        }
+    }
+
+    /// Waits until every action sent so far has been emitted.
+    pub fn flush(&self) {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if self.event_tx.send(OutputMessage::Flush(reply_tx)).is_ok() {
+            reply_rx.recv().ok();
+        }
+    }
+
+    /// Returns which keys are held and which relative axes are moving, once every action sent so
+    /// far has been emitted.
+    ///
+    /// # Example
+    /// ```
+    /// use chord2key::constants::*;
+    /// use chord2key::output::actions::*;
+    /// use chord2key::output::device::OutputDevice;
+    /// use chord2key::output::sink::NullSink;
+    ///
+    /// let device = OutputDevice::with_sink(Box::new(NullSink));
+    /// device
+    ///     .execute_event(OutputAction::Toggle(Toggle::new(Some(vec![KeyCode::KEY_A]), None)))
+    ///     .unwrap();
+    ///
+    /// let state = device.state().unwrap();
+    /// assert_eq!(state.held_keys, vec![KeyCode::KEY_A]);
+    /// assert!(state.rel_axes.is_empty());
+    /// ```
+    pub fn state(&self) -> Result<OutputState, std::io::Error> {
+        let stopped = || std::io::Error::other("The output thread has stopped");
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.event_tx
+            .send(OutputMessage::QueryState(reply_tx))
+            .map_err(|_| stopped())?;
+        reply_rx.recv().map_err(|_| stopped())
+    }
+
+    /// Sets the longest time a key may be held down before it's automatically released.
+    ///
+    /// This is a watchdog against runaway input, such as a key that stays down after its release
+    /// got lost. Keys latched down by a [Toggle] are exempt, since they're meant to stay down.
+    /// Every automatic release is logged. The watchdog is disabled with None, which is the default.
+    ///
+    /// Returns a result indicating whether the limit was successfully sent
+    pub fn set_key_hold_limit(&self, limit: Option<Duration>) -> Result<(), SendError<()>> {
+        self.event_tx
+            .send(OutputMessage::SetKeyHoldLimit(limit))
+            .map_err(|_| SendError(()))
+    }
+
+    /// Releases all held keys and mouse motion, then waits for the output thread to exit.
+    ///
+    /// Any action executed afterwards is dropped. This also happens automatically when the last
+    /// clone of the device is dropped, including while unwinding from a panic.
+    pub fn shutdown(&self) {
+        self.event_tx.send(OutputMessage::Shutdown).ok();
+
+        // A poisoned lock still holds the handle, so recover it
+        let mut thread = self.thread.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(handle) = thread.take() {
+            handle.join().ok();
+        }
     }
 
     /// Executes an [OutputAction], consuming it in the process
     ///
     /// Returns a result indicating whether the event was successfully sent
     pub fn execute_event(&self, event: OutputAction) -> Result<(), SendError<OutputAction>> {
-        self.event_tx.send(event)
+        if let Err(SendError(OutputMessage::Action(event, _sent))) = self
+            .event_tx
+            .send(OutputMessage::Action(event, Instant::now()))
+        {
+            return Err(SendError(event));
+        }
+        Ok(())
+    }
+
+    /// Creates an additional absolute pointer device used for tablet-style mouse output.
+    ///
+    /// The absolute pointer reports ABS_X and ABS_Y with the INPUT_PROP_DIRECT property, so the OS
+    /// maps its position directly onto the screen. It also advertises BTN_LEFT, so libinput treats
+    /// it as a pointer. See [OutputDevice::abs_pointer_max] for the range of its axes.
+    ///
+    /// Returns an error if the device could not be created, likely due to insufficient permissions.
+    pub fn enable_abs_pointer(&self) -> Result<(), std::io::Error> {
+        let stopped = || std::io::Error::other("The output thread has stopped");
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.event_tx
+            .send(OutputMessage::EnableAbsPointer(reply_tx))
+            .map_err(|_| stopped())?;
+        reply_rx.recv().map_err(|_| stopped())?
+    }
+
+    /// Moves the absolute pointer to the given position.
+    ///
+    /// Does nothing if [OutputDevice::enable_abs_pointer] was never called.
+    ///
+    /// Returns a result indicating whether the movement was successfully sent
+    pub fn move_abs_pointer(&self, x: AxisState, y: AxisState) -> Result<(), SendError<()>> {
+        self.event_tx
+            .send(OutputMessage::AbsPointerMove(x, y))
+            .map_err(|_| SendError(()))
     }
 }
 
@@ -88,90 +277,70 @@ impl Clone for OutputDevice {
     fn clone(&self) -> Self {
         Self {
             event_tx: self.event_tx.clone(),
+            thread: self.thread.clone(),
         }
     }
 }
 
-/// A wrapper around the [evdev::uinput::VirtualDevice] that only exposes a simple API for use.
-struct DeviceWrapper(VirtualDevice);
-
-impl DeviceWrapper {
-    /// Emit an event to the OS to press a key down.
-    pub fn down_key(&mut self, key: KeyCode) {
-        let evkey = evdev::Key::from(key);
-        let down_event = evdev::InputEvent::new(evdev::EventType::KEY, evkey.code(), 1);
-        self.0.emit(&[down_event]).unwrap();
-    }
-
-    /// Emit an event to the OS.
-    pub fn up_key(&mut self, key: KeyCode) {
-        let evkey = evdev::Key::from(key);
-        let up_event = evdev::InputEvent::new(evdev::EventType::KEY, evkey.code(), 0);
-        self.0.emit(&[up_event]).unwrap();
-    }
-
-    /// Emit an event to the OS to move a Relative Axis, such as those used in mouse input.
-    pub fn rel_axis_move(&mut self, axis: RelAxisCode, value: AxisState) {
-        let evaxis = evdev::RelativeAxisType::from(axis);
-        let axis_event = evdev::InputEvent::new(evdev::EventType::RELATIVE, evaxis.0, value);
-        self.0.emit(&[axis_event]).unwrap();
+impl Drop for OutputDevice {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.thread) == 1 {
+            self.shutdown();
+        }
     }
 }
 
 /// Turns an [OutputAction] into relevant events for the OS. Saves an internal state.
 struct VirtualOutput {
     /// The actual device emitting events.
-    pub device: DeviceWrapper,
+    pub device: Box<dyn OutputSink>,
 
     /// A saved state of all current relative axis states.
     pub rel_axes_vals: HashMap<RelAxisCode, AxisState>,
 
     /// A saved state of all current key states.
     pub key_states: HashMap<KeyCode, PressState>,
-}
 
-impl VirtualOutput {
-    /// Attempts to create a new VirtualOutput, returning a resulting containing itself.
-    ///
-    /// May fail due to lack of OS permissions.
-    pub fn init() -> std::io::Result<Self> {
-        let mut key_set = evdev::AttributeSet::<evdev::Key>::new();
-        let mut rel_axes_vals = HashMap::<RelAxisCode, AxisState>::new();
-        let mut key_states = HashMap::<KeyCode, PressState>::new();
-        KeyCode::iter().for_each(|key| {
-            key_set.insert(evdev::Key::from(key));
-            key_states.insert(key, PressState::Up);
-        });
+    /// When each currently held key was pressed down.
+    pub key_down_times: HashMap<KeyCode, Instant>,
 
-        let mut axis_set = evdev::AttributeSet::<evdev::RelativeAxisType>::new();
-        RelAxisCode::iter().for_each(|axis| {
-            axis_set.insert(evdev::RelativeAxisType::from(axis));
-            rel_axes_vals.insert(axis, 0);
-        });
+    /// The currently held keys that were latched down by a [Toggle].
+    pub latched_keys: HashSet<KeyCode>,
+}
 
-        let device = VirtualDeviceBuilder::new()?
-            .name("chord2key Device")
-            .with_keys(&key_set)?
-            .with_relative_axes(&axis_set)?
-            .build()?;
+impl VirtualOutput {
+    /// Creates a new VirtualOutput emitting to the given sink.
+    pub fn init(device: Box<dyn OutputSink>) -> Self {
+        let key_states = KeyCode::iter().map(|key| (key, PressState::Up)).collect();
+        let rel_axes_vals = RelAxisCode::iter().map(|axis| (axis, 0)).collect();
 
-        Ok(Self {
-            device: DeviceWrapper(device),
+        Self {
+            device,
             rel_axes_vals,
             key_states,
-        })
+            key_down_times: HashMap::new(),
+            latched_keys: HashSet::new(),
+        }
     }
 
     /// Emit a key press event and save the state of the key.
     fn down_key(&mut self, key: KeyCode) {
         self.device.down_key(key);
         self.key_states.insert(key, PressState::Down);
+        self.key_down_times.entry(key).or_insert_with(Instant::now);
     }
 
     /// Emit a key unpress event and save the state of the key.
     fn up_key(&mut self, key: KeyCode) {
         self.device.up_key(key);
         self.key_states.insert(key, PressState::Up);
+        self.key_down_times.remove(&key);
+        self.latched_keys.remove(&key);
+    }
+
+    /// Emit an absolute pointer movement event if an absolute pointer is enabled.
+    fn abs_pointer_move(&mut self, x: AxisState, y: AxisState) {
+        self.device.abs_pointer_move(x, y);
     }
 
     /// Emit a relative axis movement event, without saving the state of the axis.
@@ -192,6 +361,61 @@ impl VirtualOutput {
             .for_each(|(axis, val)| device.rel_axis_move(*axis, *val));
     }
 
+    /// Releases every held key that was held for longer than the limit, except for latched keys.
+    pub fn release_stuck_keys(&mut self, limit: Duration) {
+        let stuck: Vec<(KeyCode, Duration)> = self
+            .key_down_times
+            .iter()
+            .filter(|(key, _time)| !self.latched_keys.contains(key))
+            .map(|(key, time)| (*key, time.elapsed()))
+            .filter(|(_key, held)| *held > limit)
+            .collect();
+        stuck.into_iter().for_each(|(key, held)| {
+            warn!(
+                "Watchdog released {:?} after it was held for {:.1}s",
+                key,
+                held.as_secs_f64()
+            );
+            self.up_key(key);
+        });
+    }
+
+    /// Returns which keys are held and which relative axes are moving.
+    pub fn state(&self) -> OutputState {
+        let mut held_keys: Vec<KeyCode> = self
+            .key_states
+            .iter()
+            .filter(|(_key, state)| **state == PressState::Down)
+            .map(|(key, _state)| *key)
+            .collect();
+        held_keys.sort();
+
+        let mut rel_axes: Vec<(RelAxisCode, AxisState)> = self
+            .rel_axes_vals
+            .iter()
+            .filter(|(_axis, val)| **val != 0)
+            .map(|(axis, val)| (*axis, *val))
+            .collect();
+        rel_axes.sort();
+
+        OutputState {
+            held_keys,
+            rel_axes,
+        }
+    }
+
+    /// Releases every held key and stops all relative axis motion.
+    pub fn release_all(&mut self) {
+        let held: Vec<KeyCode> = self
+            .key_states
+            .iter()
+            .filter(|(_key, state)| **state == PressState::Down)
+            .map(|(key, _state)| *key)
+            .collect();
+        held.into_iter().for_each(|key| self.up_key(key));
+        self.rel_axes_vals.values_mut().for_each(|val| *val = 0);
+    }
+
     /// Emits the relevant event and saves the new state for an input [KeyStateChange].
     fn execute_keystate_change(&mut self, change: &KeyStateChange) {
         match change.state {
@@ -257,6 +481,7 @@ impl VirtualOutput {
                     }
                     PressState::Up => {
                         self.down_key(*key);
+                        self.latched_keys.insert(*key);
                     }
                 }
             }
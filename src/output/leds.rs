@@ -0,0 +1,178 @@
+use log::debug;
+use std::path::{Path, PathBuf};
+
+/// Lights on the input devices that show which configuration is active.
+pub trait LedBackend {
+    /// Turns the player LEDs on or off, starting with player 1.
+    ///
+    /// LEDs beyond the end of the pattern are turned off.
+    fn set_pattern(&mut self, pattern: &[bool]) -> Result<(), std::io::Error>;
+}
+
+/// The player LEDs exposed through the Linux LED class, such as by the hid-nintendo driver for
+/// Joy-Cons and the Pro Controller.
+///
+/// Every LED under the root whose name ends in a player number, such as
+/// `0005:057E:2009.0001:green:player-1`, is set through its `brightness` file. The LEDs are looked
+/// up again whenever a pattern is set. Unless limited with [SysfsLeds::for_devices], every
+/// connected controller shows the same pattern.
+///
+/// # Example
+/// ```
+/// use chord2key::output::leds::*;
+///
+/// let root = std::env::temp_dir().join(format!("chord2key-led-doc-{}", std::process::id()));
+/// for player in 1..=4 {
+///     let led = root.join(format!("0005:057E:2009.0001:green:player-{}", player));
+///     std::fs::create_dir_all(&led).unwrap();
+///     std::fs::write(led.join("brightness"), "0").unwrap();
+/// }
+///
+/// let mut leds = SysfsLeds::new(&root);
+/// leds.set_pattern(&[true, false, true]).unwrap();
+///
+/// let brightness = |player| {
+///     let led = root.join(format!("0005:057E:2009.0001:green:player-{}", player));
+///     std::fs::read_to_string(led.join("brightness")).unwrap()
+/// };
+/// assert_eq!(brightness(1), "1");
+/// assert_eq!(brightness(2), "0");
+/// assert_eq!(brightness(3), "1");
+/// assert_eq!(brightness(4), "0");
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SysfsLeds {
+    root: PathBuf,
+
+    /// The sysfs devices whose LEDs are set, or None to set every LED under the root.
+    devices: Option<Vec<PathBuf>>,
+}
+
+impl SysfsLeds {
+    /// The directory where the kernel exposes every LED.
+    pub const DEFAULT_ROOT: &'static str = "/sys/class/leds";
+
+    /// Creates a backend for the LEDs under the given directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            devices: None,
+        }
+    }
+
+    /// Only sets the LEDs that belong to the given sysfs devices, such as the HID devices behind
+    /// the input devices being mapped, so other controllers are left alone. See
+    /// [InputDevice::sysfs_parent](crate::input::device::InputDevice::sysfs_parent).
+    ///
+    /// The LEDs under the root are usually links into the directories of their devices. Devices
+    /// that don't exist are skipped.
+    pub fn for_devices(mut self, devices: &[PathBuf]) -> Self {
+        let devices = devices
+            .iter()
+            .filter_map(|device| device.canonicalize().ok())
+            .collect();
+        self.devices = Some(devices);
+        self
+    }
+
+    /// Returns whether the LED at the given path belongs to one of the chosen devices.
+    fn is_chosen(&self, led: &Path) -> bool {
+        match &self.devices {
+            Some(devices) => led
+                .canonicalize()
+                .map(|led| devices.iter().any(|device| led.starts_with(device)))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// Returns the player number at the end of an LED name, if any.
+    fn player_number(name: &str) -> Option<usize> {
+        let start = name.rfind("player")? + "player".len();
+        name[start..].trim_start_matches('-').parse().ok()
+    }
+
+    /// Returns the brightness file of every player LED, along with its player number.
+    fn player_leds(&self) -> Result<Vec<(usize, PathBuf)>, std::io::Error> {
+        let mut leds = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if let Some(player) = Self::player_number(&name.to_string_lossy()) {
+                if self.is_chosen(&entry.path()) {
+                    leds.push((player, entry.path().join("brightness")));
+                }
+            }
+        }
+        Ok(leds)
+    }
+}
+
+impl Default for SysfsLeds {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ROOT)
+    }
+}
+
+impl LedBackend for SysfsLeds {
+    fn set_pattern(&mut self, pattern: &[bool]) -> Result<(), std::io::Error> {
+        let leds = self.player_leds()?;
+        if leds.is_empty() {
+            debug!("No player LEDs found in {}", self.root.display());
+        }
+        for (player, brightness) in leds {
+            let on = player
+                .checked_sub(1)
+                .and_then(|i| pattern.get(i))
+                .copied()
+                .unwrap_or(false);
+            std::fs::write(brightness, if on { "1" } else { "0" })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn only_the_chosen_controllers_change() {
+        // Like sysfs, the class directory links to the LEDs of each controller's HID device
+        let sysfs = TempDir::new("sysfs");
+        let root = sysfs.join("class/leds");
+        std::fs::create_dir_all(&root).unwrap();
+        let hid = |id| sysfs.join(format!("devices/0005:057E:2009.000{}", id));
+        for id in 1..=2 {
+            for player in 1..=4 {
+                let name = format!("0005:057E:2009.000{}:green:player-{}", id, player);
+                let led = hid(id).join("leds").join(&name);
+                std::fs::create_dir_all(&led).unwrap();
+                std::fs::write(led.join("brightness"), "0").unwrap();
+                std::os::unix::fs::symlink(&led, root.join(&name)).unwrap();
+            }
+        }
+        let lit = |id| {
+            (1..=4)
+                .filter(|player| {
+                    let name = format!("0005:057E:2009.000{}:green:player-{}", id, player);
+                    let brightness = root.join(name).join("brightness");
+                    std::fs::read_to_string(brightness).unwrap() == "1"
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut leds = SysfsLeds::new(&root).for_devices(&[hid(2)]);
+        leds.set_pattern(&[true, false, true]).unwrap();
+        assert!(lit(1).is_empty());
+        assert_eq!(lit(2), [1, 3]);
+
+        // Without any device, no LED is touched
+        let mut leds = SysfsLeds::new(&root).for_devices(&[]);
+        leds.set_pattern(&[true, true, true, true]).unwrap();
+        assert!(lit(1).is_empty());
+        assert_eq!(lit(2), [1, 3]);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::constants::*;
+use crate::input::device::InputDevice;
+use crate::input::events::{InputEvent, SourcedInputEvent};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use std::collections::HashSet;
+
+/// A virtual copy of an input device that raw input events can be forwarded to.
+///
+/// This lets other programs, such as games, read a grabbed input device while chord2key is
+/// suspended.
+pub struct PassthroughDevice {
+    device: VirtualDevice,
+
+    /// The keys currently held down on the virtual device.
+    pressed: HashSet<KeyCode>,
+
+    /// The events of the frame being forwarded, emitted together once it ends.
+    frame: Vec<evdev::InputEvent>,
+}
+
+impl PassthroughDevice {
+    /// Attempts to create a virtual device with the same identity, keys and axes as the source.
+    ///
+    /// May fail due to lack of OS permissions.
+    pub fn mirror(source: &InputDevice) -> std::io::Result<Self> {
+        let source = source.evdev_device();
+        let name = format!(
+            "chord2key Passthrough {}",
+            source.name().unwrap_or("Unnamed device")
+        );
+
+        let mut builder = VirtualDeviceBuilder::new()?
+            .name(&name)
+            .input_id(source.input_id());
+        if let Some(keys) = source.supported_keys() {
+            builder = builder.with_keys(keys)?;
+        }
+        if let Some(axes) = source.supported_relative_axes() {
+            builder = builder.with_relative_axes(axes)?;
+        }
+        if let Some(axes) = source.supported_absolute_axes() {
+            let abs_state = source.get_abs_state()?;
+            for axis in axes.iter() {
+                let info = abs_state[axis.0 as usize];
+                let info = evdev::AbsInfo::new(
+                    info.value,
+                    info.minimum,
+                    info.maximum,
+                    info.fuzz,
+                    info.flat,
+                    info.resolution,
+                );
+                builder = builder.with_absolute_axis(&evdev::UinputAbsSetup::new(axis, info))?;
+            }
+        }
+
+        Ok(Self {
+            device: builder.build()?,
+            pressed: HashSet::new(),
+            frame: Vec::new(),
+        })
+    }
+
+    /// Forwards an input event to the virtual device.
+    ///
+    /// Events are held back until the end of their frame, then emitted together in a single frame,
+    /// so the events of one input frame, such as both axes of a stick, stay together.
+    pub fn emit(&mut self, ev: &SourcedInputEvent) -> std::io::Result<()> {
+        if let InputEvent::KeyEvent(kev) = &ev.event {
+            match kev.state() {
+                PressState::Down => self.pressed.insert(kev.key()),
+                PressState::Up => self.pressed.remove(&kev.key()),
+            };
+        }
+        self.frame.push((&ev.event).into());
+        if !ev.frame_end {
+            return Ok(());
+        }
+        let frame = std::mem::take(&mut self.frame);
+        self.device.emit(&frame)
+    }
+
+    /// Releases every key that is held down on the virtual device.
+    ///
+    /// Used when forwarding stops, since the matching release events will never be forwarded. Any
+    /// unfinished frame is emitted along with the releases.
+    pub fn release_all(&mut self) -> std::io::Result<()> {
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.extend(self.pressed.drain().map(|key| {
+            evdev::InputEvent::new(evdev::EventType::KEY, evdev::Key::from(key).code(), 0)
+        }));
+        if frame.is_empty() {
+            return Ok(());
+        }
+        self.device.emit(&frame)
+    }
+}
@@ -0,0 +1,113 @@
+use evdev::{FFEffect, FFEffectData, FFEffectKind, FFReplay, FFTrigger};
+use serde::{Deserialize, Serialize};
+
+/// A short rumble played on the input devices, such as to confirm a layer switch.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct RumblePattern {
+    /// The strength of the heavy motor, from 0 to 65535.
+    #[serde(default)]
+    pub strong_magnitude: u16,
+
+    /// The strength of the light motor, from 0 to 65535.
+    #[serde(default)]
+    pub weak_magnitude: u16,
+
+    /// How long the rumble lasts, in milliseconds.
+    pub duration_ms: u16,
+}
+
+impl RumblePattern {
+    /// Converts the pattern to the force feedback effect played by the kernel.
+    fn effect_data(&self) -> FFEffectData {
+        FFEffectData {
+            direction: 0,
+            trigger: FFTrigger::default(),
+            replay: FFReplay {
+                length: self.duration_ms,
+                delay: 0,
+            },
+            kind: FFEffectKind::Rumble {
+                strong_magnitude: self.strong_magnitude,
+                weak_magnitude: self.weak_magnitude,
+            },
+        }
+    }
+}
+
+/// Something that can play [RumblePattern]s.
+pub trait RumbleBackend {
+    /// Starts playing the pattern, replacing any rumble that is still playing.
+    fn rumble(&mut self, pattern: &RumblePattern) -> Result<(), std::io::Error>;
+}
+
+/// Plays every pattern on all of the backends, such as on every input device.
+///
+/// Every backend is tried, and the first error is returned.
+///
+/// # Example
+/// ```
+/// use chord2key::output::rumble::*;
+///
+/// #[derive(Default)]
+/// struct Counter(usize);
+///
+/// impl RumbleBackend for Counter {
+///     fn rumble(&mut self, _pattern: &RumblePattern) -> Result<(), std::io::Error> {
+///         self.0 += 1;
+///         Ok(())
+///     }
+/// }
+///
+/// let mut counters = vec![Counter::default(), Counter::default()];
+/// let pattern = RumblePattern {
+///     strong_magnitude: 0,
+///     weak_magnitude: 0x8000,
+///     duration_ms: 80,
+/// };
+/// counters.rumble(&pattern).unwrap();
+/// assert!(counters.iter().all(|counter| counter.0 == 1));
+/// ```
+impl<B: RumbleBackend> RumbleBackend for Vec<B> {
+    fn rumble(&mut self, pattern: &RumblePattern) -> Result<(), std::io::Error> {
+        let mut result = Ok(());
+        for backend in self.iter_mut() {
+            let played = backend.rumble(pattern);
+            if result.is_ok() {
+                result = played;
+            }
+        }
+        result
+    }
+}
+
+/// A rumble effect uploaded to an input device. See
+/// [InputDevice::upload_rumble](crate::input::device::InputDevice::upload_rumble).
+///
+/// The effect is played through the same open device as the input events, so it keeps working
+/// while the device is exclusively grabbed. It's removed from the device when dropped.
+pub struct DeviceRumble {
+    effect: FFEffect,
+}
+
+impl DeviceRumble {
+    /// The pattern uploaded before anything is played, which does nothing.
+    pub(crate) fn silent_effect() -> FFEffectData {
+        RumblePattern {
+            strong_magnitude: 0,
+            weak_magnitude: 0,
+            duration_ms: 0,
+        }
+        .effect_data()
+    }
+
+    pub(crate) fn new(effect: FFEffect) -> Self {
+        Self { effect }
+    }
+}
+
+impl RumbleBackend for DeviceRumble {
+    fn rumble(&mut self, pattern: &RumblePattern) -> Result<(), std::io::Error> {
+        self.effect.update(pattern.effect_data())?;
+        self.effect.play(1)
+    }
+}
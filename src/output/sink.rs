@@ -0,0 +1,269 @@
+use crate::constants::*;
+use crate::output::device::OutputDevice;
+use crate::strum::IntoEnumIterator;
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A single low level event emitted to an [OutputSink].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum OutputEvent {
+    /// A key changed its state.
+    Key(KeyCode, PressState),
+
+    /// A relative axis moved by the given amount.
+    RelAxis(RelAxisCode, AxisState),
+
+    /// The absolute pointer moved to the given position.
+    AbsPointer(AxisState, AxisState),
+}
+
+/// The backend that an [OutputDevice] emits its key and axis events to.
+///
+/// All state, such as which keys are held, is tracked by the [OutputDevice], so a sink only has
+/// to emit the events it's given.
+pub trait OutputSink: Send {
+    /// Emit an event to press a key down.
+    fn down_key(&mut self, key: KeyCode);
+
+    /// Emit an event to release a key.
+    fn up_key(&mut self, key: KeyCode);
+
+    /// Emit an event to move a Relative Axis, such as those used in mouse input.
+    fn rel_axis_move(&mut self, axis: RelAxisCode, value: AxisState);
+
+    /// Prepare the sink for absolute pointer events.
+    ///
+    /// Returns an error if the sink could not support them.
+    fn enable_abs_pointer(&mut self) -> std::io::Result<()>;
+
+    /// Emit an event to move the absolute pointer to a position.
+    ///
+    /// Does nothing if [OutputSink::enable_abs_pointer] was never called.
+    fn abs_pointer_move(&mut self, x: AxisState, y: AxisState);
+}
+
+/// The default [OutputSink], which emits events to the OS through uinput virtual devices.
+pub struct UinputSink {
+    device: VirtualDevice,
+
+    /// The absolute pointer device, if one was enabled.
+    abs_device: Option<VirtualDevice>,
+}
+
+impl UinputSink {
+    /// Attempts to create a new virtual keyboard + mouse device.
+    ///
+    /// May fail due to lack of OS permissions.
+    pub fn init() -> std::io::Result<Self> {
+        let mut key_set = evdev::AttributeSet::<evdev::Key>::new();
+        KeyCode::iter().for_each(|key| {
+            key_set.insert(evdev::Key::from(key));
+        });
+
+        let mut axis_set = evdev::AttributeSet::<evdev::RelativeAxisType>::new();
+        RelAxisCode::iter().for_each(|axis| {
+            axis_set.insert(evdev::RelativeAxisType::from(axis));
+        });
+
+        let device = VirtualDeviceBuilder::new()?
+            .name("chord2key Device")
+            .with_keys(&key_set)?
+            .with_relative_axes(&axis_set)?
+            .build()?;
+
+        Ok(Self {
+            device,
+            abs_device: None,
+        })
+    }
+
+    /// Attempts to create a new absolute pointer device.
+    ///
+    /// May fail due to lack of OS permissions.
+    fn init_abs_pointer() -> std::io::Result<VirtualDevice> {
+        let axis_info = evdev::AbsInfo::new(0, 0, OutputDevice::abs_pointer_max(), 0, 0, 0);
+
+        let mut prop_set = evdev::AttributeSet::<evdev::PropType>::new();
+        prop_set.insert(evdev::PropType::DIRECT);
+
+        // Without a button, libinput may not recognize the device as a pointer at all
+        let mut key_set = evdev::AttributeSet::<evdev::Key>::new();
+        key_set.insert(evdev::Key::BTN_LEFT);
+
+        VirtualDeviceBuilder::new()?
+            .name("chord2key Absolute Pointer")
+            .with_keys(&key_set)?
+            .with_absolute_axis(&evdev::UinputAbsSetup::new(
+                evdev::AbsoluteAxisType::ABS_X,
+                axis_info,
+            ))?
+            .with_absolute_axis(&evdev::UinputAbsSetup::new(
+                evdev::AbsoluteAxisType::ABS_Y,
+                axis_info,
+            ))?
+            .with_properties(&prop_set)?
+            .build()
+    }
+}
+
+impl OutputSink for UinputSink {
+    fn down_key(&mut self, key: KeyCode) {
+        let evkey = evdev::Key::from(key);
+        let down_event = evdev::InputEvent::new(evdev::EventType::KEY, evkey.code(), 1);
+        self.device.emit(&[down_event]).unwrap();
+    }
+
+    fn up_key(&mut self, key: KeyCode) {
+        let evkey = evdev::Key::from(key);
+        let up_event = evdev::InputEvent::new(evdev::EventType::KEY, evkey.code(), 0);
+        self.device.emit(&[up_event]).unwrap();
+    }
+
+    fn rel_axis_move(&mut self, axis: RelAxisCode, value: AxisState) {
+        let evaxis = evdev::RelativeAxisType::from(axis);
+        let axis_event = evdev::InputEvent::new(evdev::EventType::RELATIVE, evaxis.0, value);
+        self.device.emit(&[axis_event]).unwrap();
+    }
+
+    fn enable_abs_pointer(&mut self) -> std::io::Result<()> {
+        if self.abs_device.is_none() {
+            self.abs_device = Some(Self::init_abs_pointer()?);
+        }
+        Ok(())
+    }
+
+    fn abs_pointer_move(&mut self, x: AxisState, y: AxisState) {
+        if let Some(device) = &mut self.abs_device {
+            let x_event = evdev::InputEvent::new(
+                evdev::EventType::ABSOLUTE,
+                evdev::AbsoluteAxisType::ABS_X.0,
+                x,
+            );
+            let y_event = evdev::InputEvent::new(
+                evdev::EventType::ABSOLUTE,
+                evdev::AbsoluteAxisType::ABS_Y.0,
+                y,
+            );
+            device.emit(&[x_event, y_event]).unwrap();
+        }
+    }
+}
+
+/// An [OutputSink] that discards every event.
+///
+/// Useful for running a [Mapper](crate::mapping::mapper::Mapper) without creating any devices or
+/// needing OS permissions.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NullSink;
+
+impl OutputSink for NullSink {
+    fn down_key(&mut self, _key: KeyCode) {}
+
+    fn up_key(&mut self, _key: KeyCode) {}
+
+    fn rel_axis_move(&mut self, _axis: RelAxisCode, _value: AxisState) {}
+
+    fn enable_abs_pointer(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn abs_pointer_move(&mut self, _x: AxisState, _y: AxisState) {}
+}
+
+/// An [OutputEvent] along with the time it was emitted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RecordedEvent {
+    pub time: Instant,
+    pub event: OutputEvent,
+}
+
+/// An [OutputSink] that stores every emitted event in memory instead of sending it to the OS.
+///
+/// Clones share the same recording, so a clone can be kept to inspect the events emitted by an
+/// [OutputDevice]. This needs no OS permissions, so it's useful for tests.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::output::actions::*;
+/// use chord2key::output::device::OutputDevice;
+/// use chord2key::output::sink::*;
+///
+/// let recorder = RecordingSink::new();
+/// let device = OutputDevice::with_sink(Box::new(recorder.clone()));
+///
+/// device
+///     .execute_event(OutputAction::Pulse(Pulse::new(Some(vec![KeyCode::KEY_A]), None)))
+///     .unwrap();
+/// device.flush();
+///
+/// assert_eq!(
+///     recorder.events(),
+///     vec![
+///         OutputEvent::Key(KeyCode::KEY_A, PressState::Up),
+///         OutputEvent::Key(KeyCode::KEY_A, PressState::Down),
+///         OutputEvent::Key(KeyCode::KEY_A, PressState::Up),
+///     ]
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RecordingSink {
+    recording: Arc<Mutex<Vec<RecordedEvent>>>,
+}
+
+impl RecordingSink {
+    /// Creates a new sink with an empty recording.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&mut self, event: OutputEvent) {
+        self.recording.lock().unwrap().push(RecordedEvent {
+            time: Instant::now(),
+            event,
+        });
+    }
+
+    /// Returns every recorded event along with the time it was emitted.
+    pub fn recording(&self) -> Vec<RecordedEvent> {
+        self.recording.lock().unwrap().clone()
+    }
+
+    /// Returns every recorded event, without their timestamps.
+    pub fn events(&self) -> Vec<OutputEvent> {
+        self.recording
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|recorded| recorded.event)
+            .collect()
+    }
+
+    /// Forgets every recorded event.
+    pub fn clear(&self) {
+        self.recording.lock().unwrap().clear();
+    }
+}
+
+impl OutputSink for RecordingSink {
+    fn down_key(&mut self, key: KeyCode) {
+        self.record(OutputEvent::Key(key, PressState::Down));
+    }
+
+    fn up_key(&mut self, key: KeyCode) {
+        self.record(OutputEvent::Key(key, PressState::Up));
+    }
+
+    fn rel_axis_move(&mut self, axis: RelAxisCode, value: AxisState) {
+        self.record(OutputEvent::RelAxis(axis, value));
+    }
+
+    fn enable_abs_pointer(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn abs_pointer_move(&mut self, x: AxisState, y: AxisState) {
+        self.record(OutputEvent::AbsPointer(x, y));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::constants::*;
+
+/// Every key that types a character on a US keyboard layout, along with the character typed
+/// without and with shift held.
+const KEY_CHARS: [(KeyCode, char, char); 50] = [
+    (KeyCode::KEY_A, 'a', 'A'),
+    (KeyCode::KEY_B, 'b', 'B'),
+    (KeyCode::KEY_C, 'c', 'C'),
+    (KeyCode::KEY_D, 'd', 'D'),
+    (KeyCode::KEY_E, 'e', 'E'),
+    (KeyCode::KEY_F, 'f', 'F'),
+    (KeyCode::KEY_G, 'g', 'G'),
+    (KeyCode::KEY_H, 'h', 'H'),
+    (KeyCode::KEY_I, 'i', 'I'),
+    (KeyCode::KEY_J, 'j', 'J'),
+    (KeyCode::KEY_K, 'k', 'K'),
+    (KeyCode::KEY_L, 'l', 'L'),
+    (KeyCode::KEY_M, 'm', 'M'),
+    (KeyCode::KEY_N, 'n', 'N'),
+    (KeyCode::KEY_O, 'o', 'O'),
+    (KeyCode::KEY_P, 'p', 'P'),
+    (KeyCode::KEY_Q, 'q', 'Q'),
+    (KeyCode::KEY_R, 'r', 'R'),
+    (KeyCode::KEY_S, 's', 'S'),
+    (KeyCode::KEY_T, 't', 'T'),
+    (KeyCode::KEY_U, 'u', 'U'),
+    (KeyCode::KEY_V, 'v', 'V'),
+    (KeyCode::KEY_W, 'w', 'W'),
+    (KeyCode::KEY_X, 'x', 'X'),
+    (KeyCode::KEY_Y, 'y', 'Y'),
+    (KeyCode::KEY_Z, 'z', 'Z'),
+    (KeyCode::KEY_1, '1', '!'),
+    (KeyCode::KEY_2, '2', '@'),
+    (KeyCode::KEY_3, '3', '#'),
+    (KeyCode::KEY_4, '4', '$'),
+    (KeyCode::KEY_5, '5', '%'),
+    (KeyCode::KEY_6, '6', '^'),
+    (KeyCode::KEY_7, '7', '&'),
+    (KeyCode::KEY_8, '8', '*'),
+    (KeyCode::KEY_9, '9', '('),
+    (KeyCode::KEY_0, '0', ')'),
+    (KeyCode::KEY_MINUS, '-', '_'),
+    (KeyCode::KEY_EQUAL, '=', '+'),
+    (KeyCode::KEY_LEFTBRACE, '[', '{'),
+    (KeyCode::KEY_RIGHTBRACE, ']', '}'),
+    (KeyCode::KEY_SEMICOLON, ';', ':'),
+    (KeyCode::KEY_APOSTROPHE, '\'', '"'),
+    (KeyCode::KEY_GRAVE, '`', '~'),
+    (KeyCode::KEY_BACKSLASH, '\\', '|'),
+    (KeyCode::KEY_COMMA, ',', '<'),
+    (KeyCode::KEY_DOT, '.', '>'),
+    (KeyCode::KEY_SLASH, '/', '?'),
+    (KeyCode::KEY_SPACE, ' ', ' '),
+    (KeyCode::KEY_TAB, '\t', '\t'),
+    (KeyCode::KEY_ENTER, '\n', '\n'),
+];
+
+fn is_shift(key: &KeyCode) -> bool {
+    matches!(key, KeyCode::KEY_LEFTSHIFT | KeyCode::KEY_RIGHTSHIFT)
+}
+
+/// Returns the character typed by pressing the keys together on a US keyboard layout, if they
+/// type exactly one.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::output::text::*;
+///
+/// assert_eq!(keys_to_char(&[KeyCode::KEY_A]), Some('a'));
+/// assert_eq!(keys_to_char(&[KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_1]), Some('!'));
+/// assert_eq!(keys_to_char(&[KeyCode::KEY_LEFTCTRL, KeyCode::KEY_C]), None);
+/// assert_eq!(keys_to_char(&[KeyCode::KEY_LEFTSHIFT]), None);
+/// ```
+pub fn keys_to_char(keys: &[KeyCode]) -> Option<char> {
+    let shifted = keys.iter().any(is_shift);
+    let mut others = keys.iter().filter(|key| !is_shift(key));
+    let key = others.next()?;
+    if others.next().is_some() {
+        return None;
+    }
+    KEY_CHARS
+        .iter()
+        .find(|(k, _, _)| k == key)
+        .map(|(_, plain, shift)| if shifted { *shift } else { *plain })
+}
+
+/// Returns the keys that type the character on a US keyboard layout, with
+/// [KeyCode::KEY_LEFTSHIFT] first if it needs shift.
+///
+/// # Example
+/// ```
+/// use chord2key::constants::*;
+/// use chord2key::output::text::*;
+///
+/// assert_eq!(char_to_keys('a'), Some(vec![KeyCode::KEY_A]));
+/// assert_eq!(
+///     char_to_keys('?'),
+///     Some(vec![KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_SLASH])
+/// );
+/// assert_eq!(char_to_keys('é'), None);
+/// ```
+pub fn char_to_keys(c: char) -> Option<Vec<KeyCode>> {
+    KEY_CHARS.iter().find_map(|(key, plain, shift)| {
+        if *plain == c {
+            Some(vec![*key])
+        } else if *shift == c {
+            Some(vec![KeyCode::KEY_LEFTSHIFT, *key])
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns a printable name for a character, spelling out whitespace.
+///
+/// # Example
+/// ```
+/// use chord2key::output::text::*;
+///
+/// assert_eq!(char_name('q'), "q");
+/// assert_eq!(char_name(' '), "space");
+/// ```
+pub fn char_name(c: char) -> String {
+    match c {
+        ' ' => "space".to_string(),
+        '\t' => "tab".to_string(),
+        '\n' => "enter".to_string(),
+        c => c.to_string(),
+    }
+}